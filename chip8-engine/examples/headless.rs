@@ -0,0 +1,51 @@
+//! Runs a ROM without a window, then prints the screen as text.
+//!
+//! ```text
+//! cargo run --example headless -- <PATH> [FRAMES]
+//! ```
+
+use std::env;
+use std::fs;
+use std::process;
+
+use chip8_engine::{Machine, Quirks};
+
+/// Instructions per frame, matching the default of the desktop frontend.
+const CYCLES_PER_FRAME: usize = 10;
+const DEFAULT_FRAMES: usize = 60;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let path = args.get(1).unwrap_or_else(|| {
+        eprintln!("Usage: {} <PATH> [FRAMES]", args[0]);
+        process::exit(1);
+    });
+    let frames = match args.get(2) {
+        Some(frames) => frames.parse().unwrap_or_else(|_| {
+            eprintln!("'{}' is not a number of frames", frames);
+            process::exit(1);
+        }),
+        None => DEFAULT_FRAMES,
+    };
+
+    let program = fs::read(path).unwrap_or_else(|e| {
+        eprintln!("Unable to read '{}': {}", path, e);
+        process::exit(1);
+    });
+    let mut machine = Machine::try_new(program, Quirks::inactive()).unwrap_or_else(|e| {
+        eprintln!("Unable to load '{}': {}", path, e);
+        process::exit(1);
+    });
+
+    // No keys are held down, so programs waiting for input will sit on their title screens.
+    for _ in 0..frames {
+        if let Err(e) = machine.tick_many(&[], CYCLES_PER_FRAME) {
+            eprintln!("The program stopped after {} instructions: {}", machine.cycles(), e);
+            break;
+        }
+    }
+
+    let (width, height) = machine.resolution();
+    println!("After {} frames ({}x{}):", frames, width, height);
+    print!("{}", machine.to_ascii());
+}
@@ -0,0 +1,379 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use crate::heap;
+
+/// Every mnemonic the assembler understands, using the same names as the disassembler.
+const MNEMONICS: [&str; 32] = [
+    "END", "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "LOW", "HIGH", "PLANE", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR",
+    "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP", "AUDIO", "PITCH", "DB", "DW",
+];
+
+/// Errors raised while assembling a program, each pointing at the (1-based) source line responsible.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AsmError {
+    /// The line doesn't start with a known instruction or directive
+    UnknownMnemonic { line: usize, mnemonic: String },
+
+    /// The instruction is known, but not with this combination of operands
+    InvalidOperands { line: usize, mnemonic: String },
+
+    /// An operand looks like a number, but couldn't be parsed as one
+    InvalidNumber { line: usize, text: String },
+
+    /// A number is too large for the operand it's used in
+    ValueOutOfRange { line: usize, value: u32 },
+
+    /// A label is used without being defined anywhere in the program
+    UnknownLabel { line: usize, label: String },
+
+    /// A label is defined more than once
+    DuplicateLabel { line: usize, label: String },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => write!(f, "Line {}: unknown mnemonic '{}'", line, mnemonic),
+            AsmError::InvalidOperands { line, mnemonic } => write!(f, "Line {}: invalid operands for '{}'", line, mnemonic),
+            AsmError::InvalidNumber { line, text } => write!(f, "Line {}: '{}' is not a valid number", line, text),
+            AsmError::ValueOutOfRange { line, value } => write!(f, "Line {}: value {:#x} is out of range", line, value),
+            AsmError::UnknownLabel { line, label } => write!(f, "Line {}: unknown label '{}'", line, label),
+            AsmError::DuplicateLabel { line, label } => write!(f, "Line {}: label '{}' is already defined", line, label),
+        }
+    }
+}
+
+impl core::error::Error for AsmError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    Register(u16),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Flags,
+    Value(u32),
+    Label(String),
+    /// A 16-bit address written as `LONG NNNN`, for the two word `LD I, LONG NNNN`
+    Long(u32),
+    LongLabel(String),
+}
+
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+impl Statement<'_> {
+    /// The number of bytes this statement assembles to, which is needed to place labels before encoding.
+    fn size(&self) -> usize {
+        match self.mnemonic.as_str() {
+            "DB" => self.operands.len(),
+            "DW" => self.operands.len() * 2,
+            "LD" if self.operands.iter().any(|operand| is_long(operand)) => 4,
+            _ => 2,
+        }
+    }
+}
+
+/// Assembles CHIP-8 source into program bytes, which are expected to be loaded at `0x200`.
+///
+/// The syntax mirrors the disassembler's output (Cowgod's mnemonics), along with:
+/// - `label:` definitions, which can be used anywhere an address is expected
+/// - `DB` and `DW` directives for raw bytes and words
+/// - `LONG` addresses, as in `LD I, LONG 0x1234`, which can be used with labels as well
+/// - `;` comments
+/// - numbers in hex (`0x1F`), binary (`0b1010`) or decimal (`31`)
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut labels = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut address = heap::OFFSET_DATA;
+
+    // The first pass lays out each statement, so labels can be referenced before they're defined.
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let mut text = text.split(';').next().unwrap_or("").trim();
+
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_identifier(label) {
+                return Err(AsmError::UnknownMnemonic { line, mnemonic: label.to_string() });
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(AsmError::DuplicateLabel { line, label: label.to_string() });
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let operands: Vec<&str> = match operands.trim() {
+            "" => Vec::new(),
+            operands => operands.split(',').map(str::trim).collect(),
+        };
+        if operands.iter().any(|operand| operand.is_empty()) {
+            return Err(AsmError::InvalidOperands { line, mnemonic });
+        }
+
+        let statement = Statement { line, mnemonic, operands };
+        address += statement.size();
+        statements.push(statement);
+    }
+
+    let mut bytes = Vec::new();
+    for statement in &statements {
+        encode(statement, &labels, &mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+fn encode(statement: &Statement, labels: &BTreeMap<String, usize>, bytes: &mut Vec<u8>) -> Result<(), AsmError> {
+    use Operand::*;
+
+    let line = statement.line;
+    let mnemonic = statement.mnemonic.as_str();
+    let operands = statement.operands
+        .iter()
+        .map(|text| match parse_operand(text, line)? {
+            Label(label) => match labels.get(&label) {
+                Some(&address) => Ok(Value(address as u32)),
+                None => Err(AsmError::UnknownLabel { line, label }),
+            },
+            LongLabel(label) => match labels.get(&label) {
+                Some(&address) => Ok(Long(address as u32)),
+                None => Err(AsmError::UnknownLabel { line, label }),
+            },
+            operand => Ok(operand),
+        })
+        .collect::<Result<Vec<Operand>, AsmError>>()?;
+
+    let limit = |value: u32, max: u32| {
+        if value <= max { Ok(value as u16) } else { Err(AsmError::ValueOutOfRange { line, value }) }
+    };
+    let nibble = |value: u32| limit(value, 0xF);
+    let byte = |value: u32| limit(value, 0xFF);
+    let address = |value: u32| limit(value, 0xFFF);
+
+    // Data directives take any number of operands, so they're written out directly.
+    match mnemonic {
+        "DB" | "DW" => {
+            for operand in &operands {
+                match (mnemonic, operand) {
+                    ("DB", Value(value)) => bytes.push(byte(*value)? as u8),
+                    ("DW", Value(value)) => bytes.extend_from_slice(&limit(*value, 0xFFFF)?.to_be_bytes()),
+                    _ => return Err(AsmError::InvalidOperands { line, mnemonic: mnemonic.to_string() }),
+                }
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+    // `IStoreLongAddress` is the only instruction which takes up two words.
+    if let ("LD", [I, Long(nnnn)]) = (mnemonic, operands.as_slice()) {
+        bytes.extend_from_slice(&0xF000u16.to_be_bytes());
+        bytes.extend_from_slice(&limit(*nnnn, 0xFFFF)?.to_be_bytes());
+        return Ok(());
+    }
+
+    let opcode = match (mnemonic, operands.as_slice()) {
+        ("END", []) => 0x0000,
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [Value(n)]) => 0x00C0 | nibble(*n)?,
+        ("SCU", [Value(n)]) => 0x00D0 | nibble(*n)?,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("PLANE", [Value(n)]) => 0xF001 | nibble(*n)? << 8,
+        ("SYS", [Value(nnn)]) => address(*nnn)?,
+        ("JP", [Value(nnn)]) => 0x1000 | address(*nnn)?,
+        ("JP", [Register(0), Value(nnn)]) => 0xB000 | address(*nnn)?,
+        ("CALL", [Value(nnn)]) => 0x2000 | address(*nnn)?,
+        ("SE", [Register(x), Value(kk)]) => 0x3000 | x << 8 | byte(*kk)?,
+        ("SNE", [Register(x), Value(kk)]) => 0x4000 | x << 8 | byte(*kk)?,
+        ("SE", [Register(x), Register(y)]) => 0x5000 | x << 8 | y << 4,
+        ("LD", [Register(x), Value(kk)]) => 0x6000 | x << 8 | byte(*kk)?,
+        ("ADD", [Register(x), Value(kk)]) => 0x7000 | x << 8 | byte(*kk)?,
+        ("LD", [Register(x), Register(y)]) => 0x8000 | x << 8 | y << 4,
+        ("OR", [Register(x), Register(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [Register(x), Register(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [Register(x), Register(y)]) => 0x8003 | x << 8 | y << 4,
+        ("ADD", [Register(x), Register(y)]) => 0x8004 | x << 8 | y << 4,
+        ("SUB", [Register(x), Register(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [Register(x)]) => 0x8006 | x << 8 | x << 4,
+        ("SHR", [Register(x), Register(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SUBN", [Register(x), Register(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [Register(x)]) => 0x800E | x << 8 | x << 4,
+        ("SHL", [Register(x), Register(y)]) => 0x800E | x << 8 | y << 4,
+        ("SNE", [Register(x), Register(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", [I, Value(nnn)]) => 0xA000 | address(*nnn)?,
+        ("RND", [Register(x), Value(kk)]) => 0xC000 | x << 8 | byte(*kk)?,
+        ("DRW", [Register(x), Register(y), Value(n)]) => 0xD000 | x << 8 | y << 4 | nibble(*n)?,
+        ("SKP", [Register(x)]) => 0xE09E | x << 8,
+        ("SKNP", [Register(x)]) => 0xE0A1 | x << 8,
+        ("AUDIO", []) => 0xF002,
+        ("PITCH", [Register(x)]) => 0xF03A | x << 8,
+        ("LD", [Register(x), DelayTimer]) => 0xF007 | x << 8,
+        ("LD", [Register(x), Key]) => 0xF00A | x << 8,
+        ("LD", [DelayTimer, Register(x)]) => 0xF015 | x << 8,
+        ("LD", [SoundTimer, Register(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, Register(x)]) => 0xF01E | x << 8,
+        ("LD", [Font, Register(x)]) => 0xF029 | x << 8,
+        ("LD", [BigFont, Register(x)]) => 0xF030 | x << 8,
+        ("LD", [Bcd, Register(x)]) => 0xF033 | x << 8,
+        ("LD", [IndirectI, Register(x)]) => 0xF055 | x << 8,
+        ("LD", [Register(x), IndirectI]) => 0xF065 | x << 8,
+        ("LD", [Flags, Register(x)]) => 0xF075 | x << 8,
+        ("LD", [Register(x), Flags]) => 0xF085 | x << 8,
+        _ if MNEMONICS.contains(&mnemonic) =>
+            return Err(AsmError::InvalidOperands { line, mnemonic: mnemonic.to_string() }),
+        _ => return Err(AsmError::UnknownMnemonic { line, mnemonic: mnemonic.to_string() }),
+    };
+
+    bytes.extend_from_slice(&opcode.to_be_bytes());
+    Ok(())
+}
+
+fn parse_operand(text: &str, line: usize) -> Result<Operand, AsmError> {
+    let upper = text.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DelayTimer,
+        "ST" => Operand::SoundTimer,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "HF" => Operand::BigFont,
+        "R" => Operand::Flags,
+        "B" => Operand::Bcd,
+        _ if is_long(text) => match parse_operand(text[4..].trim(), line)? {
+            Operand::Value(value) => Operand::Long(value),
+            Operand::Label(label) => Operand::LongLabel(label),
+            _ => return Err(AsmError::InvalidNumber { line, text: text.to_string() }),
+        },
+        _ if upper.len() == 2 && upper.starts_with('V') => match u16::from_str_radix(&upper[1..], 16) {
+            Ok(register) => Operand::Register(register),
+            Err(_) => Operand::Label(text.to_string()),
+        },
+        _ if upper.starts_with(|c: char| c.is_ascii_digit()) => {
+            let value = if let Some(hex) = upper.strip_prefix("0X") {
+                u32::from_str_radix(hex, 16)
+            } else if let Some(binary) = upper.strip_prefix("0B") {
+                u32::from_str_radix(binary, 2)
+            } else {
+                upper.parse()
+            };
+            Operand::Value(value.map_err(|_| AsmError::InvalidNumber { line, text: text.to_string() })?)
+        }
+        _ if is_identifier(text) => Operand::Label(text.to_string()),
+        _ => return Err(AsmError::InvalidNumber { line, text: text.to_string() }),
+    };
+    Ok(operand)
+}
+
+/// Whether the operand is a `LONG` address, which makes its instruction two words long.
+fn is_long(text: &str) -> bool {
+    text.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case("LONG")) && text[4..].starts_with(char::is_whitespace)
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction;
+    use crate::instruction::Instruction::*;
+    use crate::registers::Register;
+
+    fn decode_all(bytes: &[u8]) -> Vec<Instruction> {
+        (0..bytes.len()).step_by(2).map(|pc| Instruction::new(bytes, pc)).collect()
+    }
+
+    #[test]
+    pub fn test_assemble() {
+        let source = "
+            ; Draw the digit in V0, then wait for a key and draw that instead
+            start:
+                CLS
+                LD V0, 0x0A
+                LD V1, 10       ; x
+                LD V2, 0b1000   ; y
+            loop:
+                LD F, V0
+                DRW V1, V2, 5
+                LD V0, K
+                CALL clear
+                JP loop
+            clear: CLS
+                RET
+        ";
+        let bytes = assemble(source).unwrap();
+        assert_eq!(bytes, vec![
+            0x00, 0xE0, 0x60, 0x0A, 0x61, 0x0A, 0x62, 0x08,
+            0xF0, 0x29, 0xD1, 0x25, 0xF0, 0x0A, 0x22, 0x12, 0x12, 0x08,
+            0x00, 0xE0, 0x00, 0xEE,
+        ]);
+
+        let v = Register::new;
+        assert_eq!(decode_all(&bytes), vec![
+            ClearScreen,
+            RegisterValueStore { register: v(0), value: 0x0A },
+            RegisterValueStore { register: v(1), value: 10 },
+            RegisterValueStore { register: v(2), value: 8 },
+            IStoreDigitAddress { register: v(0) },
+            DrawSprite { register_x: v(1), register_y: v(2), sprite_height: 5 },
+            WaitForAnyKey { register: v(0) },
+            CallSubroutine { address: 0x212 },
+            Goto { address: 0x208 },
+            ClearScreen,
+            ReturnSubroutine,
+        ]);
+    }
+
+    #[test]
+    pub fn test_disassembly_round_trip() {
+        let opcodes: [u16; 55] = [
+            0x00E0, 0x00EE, 0x00C3, 0x00D3, 0x00FB, 0x00FC, 0x00FE, 0x00FF, 0xF301, 0x1234, 0x2345, 0x3A11, 0x4A11, 0x5AB0,
+            0x6A11, 0x7A11, 0x8AB0, 0x8AB1, 0x8AB2, 0x8AB3, 0x8AB4, 0x8AB5, 0x8AB6, 0x8AB7, 0x8ABE, 0x9AB0,
+            0xA123, 0xB123, 0xCA11, 0xDAB5, 0xEA9E, 0xEAA1, 0xF002, 0xFA3A, 0xFA07, 0xFA0A, 0xFA15, 0xFA18, 0xFA1E, 0xFA29, 0xFA30,
+            0xFA33, 0xFA55, 0xFA65, 0xF775, 0xF385, 0x5AB1, 0x90F1, 0x0123, 0xE000, 0xF0FF, 0x800F, 0x0000, 0xF165, 0xD010,
+        ];
+        let bytes: Vec<u8> = opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+        let source: Vec<String> = decode_all(&bytes).iter().map(|instruction| instruction.to_string()).collect();
+        assert_eq!(assemble(&source.join("\n")).unwrap(), bytes);
+    }
+
+    #[test]
+    pub fn test_directives() {
+        let bytes = assemble("LD I, sprite\nsprite: DB 0xF0, 0x90, 144\nDW 0x1234").unwrap();
+        assert_eq!(bytes, vec![0xA2, 0x02, 0xF0, 0x90, 0x90, 0x12, 0x34]);
+
+        let bytes = assemble("LD I, LONG 0xABCD\nLD I, long sprite\nsprite: DB 0xFF").unwrap();
+        assert_eq!(bytes, vec![0xF0, 0x00, 0xAB, 0xCD, 0xF0, 0x00, 0x02, 0x08, 0xFF]);
+    }
+
+    #[test]
+    pub fn test_errors() {
+        assert_eq!(assemble("CLS\nFOO V0"), Err(AsmError::UnknownMnemonic { line: 2, mnemonic: "FOO".to_string() }));
+        assert_eq!(assemble("LD V0"), Err(AsmError::InvalidOperands { line: 1, mnemonic: "LD".to_string() }));
+        assert_eq!(assemble("LD V0, 0xZZ"), Err(AsmError::InvalidNumber { line: 1, text: "0xZZ".to_string() }));
+        assert_eq!(assemble("LD V0, 256"), Err(AsmError::ValueOutOfRange { line: 1, value: 256 }));
+        assert_eq!(assemble("JP nowhere"), Err(AsmError::UnknownLabel { line: 1, label: "nowhere".to_string() }));
+        assert_eq!(assemble("a: CLS\na: CLS"), Err(AsmError::DuplicateLabel { line: 2, label: "a".to_string() }));
+    }
+}
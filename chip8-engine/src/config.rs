@@ -0,0 +1,50 @@
+use crate::{display, heap, stack};
+
+/// Emulator settings which aren't covered by `Quirks`, such as the hardware parameters
+/// which differ between CHIP-8 platforms.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MachineConfig {
+    /// Size of the addressable memory in bytes: 4096 for CHIP-8 and SUPER-CHIP, 65536 for XO-CHIP
+    pub memory_size: usize,
+    /// The display's low resolution as `(width, height)`, which is doubled in the SUPER-CHIP high resolution mode.
+    /// CHIP-8 uses 64x32, but some variants have other screen sizes.
+    pub resolution: (usize, usize),
+    /// Where the program is copied into memory and starts running from: `0x200` for most platforms,
+    /// but `0x600` for the ETI-660
+    pub load_address: usize,
+    /// How many subroutine calls can be nested before `MachineError::StackOverflow`. CHIP-8 allows 16, but some
+    /// SUPER-CHIP implementations and recursive programs need more
+    pub stack_capacity: usize,
+    /// Unknown opcodes halt the machine with `MachineError::UnknownOpcode`, instead of being logged and skipped.
+    /// Calls to machine code routines with `0NNN` halt it with `MachineError::SysCall`, instead of being ignored.
+    pub is_strict_opcodes: bool,
+    /// What happens when the program counter reaches `0x0000`, which is what uninitialized memory decodes to
+    pub zero_opcode: ZeroOpcode,
+}
+
+/// How the `0x0000` opcode is executed. It isn't a real CHIP-8 instruction, but a program which runs
+/// past its end lands on zeroed memory, so treating it as the end of the program is a useful default.
+///
+/// The non-standard `0x00DE` opcode always ends the program, regardless of this setting.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ZeroOpcode {
+    /// The machine halts in place, as with `Instruction::EndProgram`
+    Halt,
+    /// The machine stops with `MachineError::ZeroOpcode`
+    Error,
+    /// The opcode is handled like any other unknown opcode, according to `is_strict_opcodes`
+    Unknown,
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        MachineConfig {
+            memory_size: heap::MEMORY_SIZE,
+            resolution: (display::PIXELS_H, display::PIXELS_V),
+            load_address: heap::OFFSET_DATA,
+            stack_capacity: stack::DEFAULT_CAPACITY,
+            is_strict_opcodes: false,
+            zero_opcode: ZeroOpcode::Halt,
+        }
+    }
+}
@@ -1,57 +1,822 @@
-const PIXELS_H: usize = 64;
-const PIXELS_V: usize = 32;
-const BUFFER_SIZE: usize = PIXELS_H * PIXELS_V;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub const PIXELS_H: usize = 64;
+pub const PIXELS_V: usize = 32;
+/// The SUPER-CHIP high resolution mode doubles the resolution in each direction.
+const HIGH_RES_SCALE: usize = 2;
+const SCROLL_H_AMOUNT: usize = 4;
+pub const SPRITE_WIDTH: usize = 8;
+pub const LARGE_SPRITE_WIDTH: usize = 16;
+
+/// XO-CHIP has two drawing planes, so each pixel holds a 2-bit value.
+const PLANE_COUNT: usize = 2;
+const ALL_PLANES: u8 = 0b11;
+
+/// Flags in the first byte of a serialized display.
+const SERIALIZED_HIGH_RES: u8 = 0b01;
+const SERIALIZED_SECOND_PLANE: u8 = 0b10;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DisplayError {
+    InvalidData { len: usize },
+}
 
 const DARK_COLOR: (u8, u8, u8) = (0, 33, 66);
 const LIGHT_COLOR: (u8, u8, u8) = (0, 128, 255);
+const PLANE_2_COLOR: (u8, u8, u8) = (255, 102, 0);
+const BOTH_PLANES_COLOR: (u8, u8, u8) = (255, 204, 0);
 
+/// The resolution defaults to 64x32, or 128x64 in the SUPER-CHIP high resolution mode, but can be changed
+/// for CHIP-8 variants with other screen sizes. The high resolution mode is always double the low one.
+/// The buffer is always sized for the high resolution mode, but only the first `width * height` pixels
+/// are in use for the active mode.
+///
+/// Each pixel stores one bit per XO-CHIP plane, and drawing only affects the planes selected
+/// by the plane mask. The mask defaults to the first plane, which behaves like a plain
+/// monochrome CHIP-8 display.
+///
+/// Switching between resolutions clears the screen, so sprites drawn after a switch
+/// can only collide with pixels that were drawn in the new mode. This holds even when
+/// the switch happens in the middle of a frame.
 pub struct Display {
-    bits: [bool; BUFFER_SIZE]
+    /// The low resolution as `(width, height)`
+    resolution: (usize, usize),
+    bits: Vec<u8>,
+    is_high_res: bool,
+    plane_mask: u8,
+    /// The color for each pixel value, so index 0 is unlit and index 3 is lit on both planes
+    palette: [(u8, u8, u8); 4],
+    /// How many frames a pixel keeps glowing after it's turned off, to hide XOR flicker
+    persistence: u8,
+    /// Frames left until each unlit pixel has fully faded, counted down by `end_frame`
+    decay: Vec<u16>,
+    /// The value each pixel had when it was last lit at the end of a frame, which it fades from
+    glow: Vec<u8>,
+    /// Swaps the colors of unlit and lit pixels on output, without changing the pixels themselves
+    is_inverted: bool,
+    /// Scales every output color, from 0.0 (black) to 1.0 (the palette as-is)
+    brightness: f32,
+    /// Whether the output has changed since it was last drawn by `draw_if_dirty`
+    is_dirty: bool,
 }
 
 impl Display {
+    #[cfg(test)]
     pub fn new() -> Self {
+        Display::with_resolution(PIXELS_H, PIXELS_V)
+    }
+
+    /// Creates a display with a low resolution of `width` by `height` pixels, and twice that in high resolution.
+    pub fn with_resolution(width: usize, height: usize) -> Self {
+        let buffer_size = Display::buffer_size(width, height);
         Display {
-            bits: [false; BUFFER_SIZE]
+            resolution: (width, height),
+            bits: vec![0; buffer_size],
+            is_high_res: false,
+            plane_mask: 1,
+            palette: [DARK_COLOR, LIGHT_COLOR, PLANE_2_COLOR, BOTH_PLANES_COLOR],
+            persistence: 0,
+            decay: vec![0; buffer_size],
+            glow: vec![0; buffer_size],
+            is_inverted: false,
+            brightness: 1.0,
+            is_dirty: true,
         }
     }
 
+    /// Sets the RGB colors used for lit (`foreground`) and unlit (`background`) pixels on the first plane.
+    pub fn set_colors(&mut self, foreground: (u8, u8, u8), background: (u8, u8, u8)) {
+        self.palette[1] = foreground;
+        self.palette[0] = background;
+        self.is_dirty = true;
+    }
+
+    /// Sets the RGB colors for each pixel value, indexed by the bits of the planes it's lit on.
+    pub fn set_palette(&mut self, palette: [(u8, u8, u8); 4]) {
+        self.palette = palette;
+        self.is_dirty = true;
+    }
+
+    pub fn is_inverted(&self) -> bool {
+        self.is_inverted
+    }
+
+    /// Swaps the colors of pixels lit on the first plane with unlit ones, e.g. for a dark-on-light display.
+    /// On XO-CHIP displays the colors of pixels lit on both planes swap with those lit only on the second.
+    pub fn set_inverted(&mut self, is_inverted: bool) {
+        self.is_inverted = is_inverted;
+        self.is_dirty = true;
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    /// Dims every output color by `brightness`, which is clamped to between 0.0 (black) and 1.0 (full brightness).
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        self.is_dirty = true;
+    }
+
+    /// Keeps pixels which turn off partially lit for `frames` frames, blending toward the background as they fade.
+    /// The default of 0 turns pixels off immediately.
+    pub fn set_persistence(&mut self, frames: u8) {
+        self.persistence = frames;
+        self.is_dirty = true;
+    }
+
+    /// Counts down the fade of unlit pixels, once per 60Hz frame.
+    pub fn end_frame(&mut self) {
+        let lit_decay = self.persistence as u16 + 1;
+        for index in 0..(self.width() * self.height()) {
+            if self.bits[index] != 0 {
+                self.glow[index] = self.bits[index];
+                self.decay[index] = lit_decay;
+            } else if self.decay[index] > 0 {
+                self.decay[index] -= 1;
+                self.is_dirty |= self.persistence > 0;
+            }
+        }
+    }
+
+    pub fn plane_mask(&self) -> u8 {
+        self.plane_mask
+    }
+
+    /// Selects the planes affected by drawing, clearing and scrolling, with one bit per plane.
+    pub fn set_plane_mask(&mut self, mask: u8) {
+        self.plane_mask = mask & ALL_PLANES;
+    }
+
+    /// The number of selected planes, each of which takes its own sprite data when drawing.
+    pub fn plane_count(&self) -> usize {
+        self.plane_mask.count_ones() as usize
+    }
+
+    fn buffer_size(width: usize, height: usize) -> usize {
+        assert!(width > 0 && height > 0, "The display must have at least one pixel");
+        width * HIGH_RES_SCALE * height * HIGH_RES_SCALE
+    }
+
+    pub fn width(&self) -> usize {
+        if self.is_high_res { self.resolution.0 * HIGH_RES_SCALE } else { self.resolution.0 }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.is_high_res { self.resolution.1 * HIGH_RES_SCALE } else { self.resolution.1 }
+    }
+
+    pub fn is_high_res(&self) -> bool {
+        self.is_high_res
+    }
+
+    pub fn set_high_res(&mut self, is_high_res: bool) {
+        if self.is_high_res != is_high_res {
+            self.is_high_res = is_high_res;
+            self.clear_all();
+        }
+    }
+
+    /// Clears the selected planes.
     pub fn clear(&mut self) {
-        self.bits.fill(false);
+        let mask = self.plane_mask;
+        self.bits.iter_mut().for_each(|pixel| *pixel &= !mask);
+        self.is_dirty = true;
+    }
+
+    /// Clears every plane, regardless of which are selected.
+    pub fn clear_all(&mut self) {
+        self.bits.fill(0);
+        self.decay.fill(0);
+        self.is_dirty = true;
+    }
+
+    /// The pixels of the active resolution in row-major order, as a bit for each plane they're lit on.
+    pub fn pixels(&self) -> &[u8] {
+        &self.bits[..(self.width() * self.height())]
+    }
+
+    /// The `(x, y)` positions of the pixels which differ from `previous`, a copy of `pixels` taken earlier.
+    /// Every pixel is reported if `previous` was taken at a different resolution.
+    pub fn dirty_since(&self, previous: &[u8]) -> Vec<(usize, usize)> {
+        let width = self.width();
+        let pixels = self.pixels();
+        let is_resized = previous.len() != pixels.len();
+        pixels
+            .iter()
+            .enumerate()
+            .filter(|&(index, &pixel)| is_resized || previous[index] != pixel)
+            .map(|(index, _)| (index % width, index / width))
+            .collect()
+    }
+
+    /// Replaces the resolution mode and pixels, as laid out by `pixels`, leaving the other settings as-is.
+    /// Fails without changing anything if there isn't exactly one pixel for each position in that mode.
+    pub fn load_pixels(&mut self, is_high_res: bool, pixels: &[u8]) -> Result<(), DisplayError> {
+        if pixels.len() != self.pixel_count(is_high_res) {
+            return Err(DisplayError::InvalidData { len: pixels.len() });
+        }
+
+        self.set_high_res(is_high_res);
+        self.bits[..pixels.len()].copy_from_slice(pixels);
+        self.is_dirty = true;
+        Ok(())
+    }
+
+    /// The number of pixels in use in the given resolution mode.
+    fn pixel_count(&self, is_high_res: bool) -> usize {
+        let scale = if is_high_res { HIGH_RES_SCALE } else { 1 };
+        self.resolution.0 * scale * self.resolution.1 * scale
+    }
+
+    /// Replaces the selected planes of the pixel at `to` with those of `from`, or unsets them if there's no source.
+    fn move_pixel(&mut self, from: Option<usize>, to: usize) {
+        let mask = self.plane_mask;
+        let source = from.map_or(0, |from| self.bits[from]);
+        self.bits[to] = (self.bits[to] & !mask) | (source & mask);
+        self.is_dirty = true;
+    }
+
+    /// Moves every row down by `amount` pixels. Rows scrolled off the bottom are lost.
+    pub fn scroll_down(&mut self, amount: usize) {
+        let (width, height) = (self.width(), self.height());
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let from = (y >= amount).then(|| (width * (y - amount)) + x);
+                self.move_pixel(from, (width * y) + x);
+            }
+        }
+    }
+
+    /// Moves every row up by `amount` pixels. Rows scrolled off the top are lost.
+    pub fn scroll_up(&mut self, amount: usize) {
+        let (width, height) = (self.width(), self.height());
+        for y in 0..height {
+            for x in 0..width {
+                let from = (y + amount < height).then(|| (width * (y + amount)) + x);
+                self.move_pixel(from, (width * y) + x);
+            }
+        }
+    }
+
+    /// Moves every column right by 4 pixels. Columns scrolled off the right edge are lost.
+    pub fn scroll_right(&mut self) {
+        let width = self.width();
+        for y in 0..self.height() {
+            for x in (0..width).rev() {
+                let from = (x >= SCROLL_H_AMOUNT).then(|| (width * y) + x - SCROLL_H_AMOUNT);
+                self.move_pixel(from, (width * y) + x);
+            }
+        }
     }
 
+    /// Moves every column left by 4 pixels. Columns scrolled off the left edge are lost.
+    pub fn scroll_left(&mut self) {
+        let width = self.width();
+        for y in 0..self.height() {
+            for x in 0..width {
+                let from = (x + SCROLL_H_AMOUNT < width).then(|| (width * y) + x + SCROLL_H_AMOUNT);
+                self.move_pixel(from, (width * y) + x);
+            }
+        }
+    }
+
+    /// Toggles the pixel on the selected planes, returning whether a lit pixel was erased.
+    #[cfg(test)]
     pub fn set_pixel(&mut self, x: usize, y: usize, value: bool) -> bool {
-        if x < PIXELS_H && y < PIXELS_V {
-            let index = (PIXELS_H * y) + x;
-            let is_collision = self.bits[index] && value;
-            self.bits[index] ^= value;
+        self.toggle_pixel(x, y, if value { self.plane_mask } else { 0 })
+    }
+
+    fn toggle_pixel(&mut self, x: usize, y: usize, planes: u8) -> bool {
+        if x < self.width() && y < self.height() {
+            let index = (self.width() * y) + x;
+            let is_collision = self.bits[index] & planes != 0;
+            self.bits[index] ^= planes;
+            self.is_dirty |= planes != 0;
             return is_collision;
         }
         false
     }
 
-    pub fn render_sprite(&mut self, start_x: usize, start_y: usize, sprite: &[u8]) -> bool {
-        let mut is_collision = false;
-        for y in 0..sprite.len() {
-            let row = sprite[y];
-            for x in 0..8usize {
-                let inverse = 7 - x;
-                let is_lit = (row & (1u8 << inverse)) != 0;
-                is_collision |= self.set_pixel(start_x + x, start_y + y, is_lit);
+    /// Draws a sprite by XORing it onto the screen, returning whether any lit pixels were erased.
+    /// Pixels past the right or bottom edge of the screen are clipped, unless `is_wrap_x` or `is_wrap_y` is set
+    /// for that axis, in which case they wrap around to the opposite edge and can collide there.
+    ///
+    /// Each row is `sprite_width` pixels wide, which is 8 for regular sprites and 16 for SUPER-CHIP large sprites,
+    /// and is read as that many bits starting from the most significant bit of its first byte.
+    /// The sprite holds consecutive data for each selected plane, starting with the lowest,
+    /// so drawing to both planes takes twice as many bytes as drawing to one.
+    pub fn render_sprite(&mut self, start_x: usize, start_y: usize, sprite: &[u8], sprite_width: usize, is_wrap_x: bool, is_wrap_y: bool) -> bool {
+        self.render_sprite_counted(start_x, start_y, sprite, sprite_width, is_wrap_x, is_wrap_y) > 0
+    }
+
+    /// Like `render_sprite`, but returns how many lit pixels were erased, for debuggers which show more than whether
+    /// the sprite collided. A pixel erased on both planes counts once for each.
+    pub fn render_sprite_counted(&mut self, start_x: usize, start_y: usize, sprite: &[u8], sprite_width: usize, is_wrap_x: bool, is_wrap_y: bool) -> u32 {
+        assert!(sprite_width == SPRITE_WIDTH || sprite_width == LARGE_SPRITE_WIDTH, "Sprites must be 8 or 16 pixels wide");
+        let row_bytes = sprite_width / 8;
+        let plane_count = self.plane_count();
+        if plane_count == 0 || sprite.is_empty() {
+            return 0;
+        }
+
+        let (width, height) = (self.width(), self.height());
+        let planes = (0..PLANE_COUNT).map(|plane| 1u8 << plane).filter(|plane| self.plane_mask & plane != 0);
+        let plane_sprites: Vec<(u8, &[u8])> = planes.zip(sprite.chunks(sprite.len() / plane_count)).collect();
+
+        let mut erased_count = 0;
+        for (plane, plane_sprite) in plane_sprites {
+            for (y, row) in plane_sprite.chunks(row_bytes).enumerate() {
+                for x in 0..(row.len() * 8) {
+                    let inverse = 7 - (x % 8);
+                    if (row[x / 8] & (1u8 << inverse)) == 0 {
+                        continue;
+                    }
+                    let pixel_x = if is_wrap_x { (start_x + x) % width } else { start_x + x };
+                    let pixel_y = if is_wrap_y { (start_y + y) % height } else { start_y + y };
+                    erased_count += self.toggle_pixel(pixel_x, pixel_y, plane) as u32;
+                }
             }
         }
-        is_collision
+        erased_count
     }
 
+    /// Writes the active resolution into an RGBA buffer of `width * height * 4` bytes.
     pub fn draw(&self, buffer: &mut [u8]) {
-        for i in 0..BUFFER_SIZE {
-            let is_pixel_set = self.bits[i];
+        let rgba = self.to_rgba();
+        buffer[..rgba.len()].copy_from_slice(&rgba);
+    }
+
+    /// Like `draw`, but only writes to `buffer` if the output has changed since the last call,
+    /// returning whether it did. The first call always draws.
+    pub fn draw_if_dirty(&mut self, buffer: &mut [u8]) -> bool {
+        if !self.is_dirty {
+            return false;
+        }
+        self.draw(buffer);
+        self.is_dirty = false;
+        true
+    }
+
+    /// Renders the active resolution as RGBA bytes in row-major order.
+    /// Unlit pixels which are still fading are blended between the background and the color they last had.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.pixels()
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &pixel)| {
+                let color = if pixel == 0 && self.persistence > 0 {
+                    self.faded_color(index)
+                } else {
+                    self.color(pixel)
+                };
+                let dim = |channel: u8| (channel as f32 * self.brightness) as u8;
+                [dim(color.0), dim(color.1), dim(color.2), 255]
+            })
+            .collect()
+    }
+
+    fn faded_color(&self, index: usize) -> (u8, u8, u8) {
+        // A pixel turned off mid-frame hasn't started fading yet, so it's capped at the brightest faded step.
+        let steps = self.persistence as i32 + 1;
+        let remaining = (self.decay[index] as i32).min(self.persistence as i32);
+        let (lit, unlit) = (self.color(self.glow[index]), self.color(0));
+        let blend = |lit: u8, unlit: u8| (unlit as i32 + (lit as i32 - unlit as i32) * remaining / steps) as u8;
+        (blend(lit.0, unlit.0), blend(lit.1, unlit.1), blend(lit.2, unlit.2))
+    }
+
+    fn color(&self, pixel: u8) -> (u8, u8, u8) {
+        let pixel = if self.is_inverted {pixel ^ 1} else {pixel};
+        self.palette[pixel as usize]
+    }
+
+    /// Renders the active resolution as text, with `#` for pixels lit on any plane and a space for unlit ones.
+    /// Every row, including the last, ends in a newline.
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity((self.width() + 1) * self.height());
+        for row in self.pixels().chunks(self.width()) {
+            ascii.extend(row.iter().map(|&pixel| if pixel != 0 {'#'} else {' '}));
+            ascii.push('\n');
+        }
+        ascii
+    }
+
+    /// Packs the pixels of the active resolution into as few bytes as possible, for sending to a remote client.
+    ///
+    /// The first byte has bit 0 set in high resolution, and bit 1 set if any pixel is lit on the second XO-CHIP plane.
+    /// It's followed by the pixels of the first plane in row-major order with 8 pixels per byte, starting from the
+    /// most significant bit like sprites are, and then the second plane in the same layout if it's in use.
+    /// A plain CHIP-8 screen takes 257 bytes, compared to 8192 as RGBA.
+    pub fn serialize(&self) -> Vec<u8> {
+        let pixels = self.pixels();
+        let is_second_plane = pixels.iter().any(|&pixel| pixel & 0b10 != 0);
+        let mut header = 0;
+        if self.is_high_res { header |= SERIALIZED_HIGH_RES; }
+        if is_second_plane { header |= SERIALIZED_SECOND_PLANE; }
+
+        let mut data = vec![header];
+        for plane in 0..(if is_second_plane { PLANE_COUNT } else { 1 }) {
+            data.extend(pixels.chunks(8).map(|chunk| {
+                chunk.iter().enumerate().fold(0, |byte, (bit, &pixel)| byte | (((pixel >> plane) & 1) << (7 - bit)))
+            }));
+        }
+        data
+    }
 
-            let i = i * 4;
-            buffer[i + 0] = if is_pixel_set {LIGHT_COLOR.0} else {DARK_COLOR.0};
-            buffer[i + 1] = if is_pixel_set {LIGHT_COLOR.1} else {DARK_COLOR.1};
-            buffer[i + 2] = if is_pixel_set {LIGHT_COLOR.2} else {DARK_COLOR.2};
-            buffer[i + 3] = 255;
+    /// Replaces the resolution mode and pixels with those written by `serialize`, leaving the other settings as-is.
+    /// Fails if the data wasn't serialized from a display with the same resolution.
+    pub fn deserialize(&mut self, data: &[u8]) -> Result<(), DisplayError> {
+        let error = DisplayError::InvalidData { len: data.len() };
+        let (&header, planes) = data.split_first().ok_or(error)?;
+        if header & !(SERIALIZED_HIGH_RES | SERIALIZED_SECOND_PLANE) != 0 {
+            return Err(error);
         }
+
+        let is_high_res = header & SERIALIZED_HIGH_RES != 0;
+        let plane_count = if header & SERIALIZED_SECOND_PLANE != 0 { PLANE_COUNT } else { 1 };
+        let pixel_count = self.pixel_count(is_high_res);
+        let plane_len = pixel_count.div_ceil(8);
+        if planes.len() != plane_len * plane_count {
+            return Err(error);
+        }
+
+        self.set_high_res(is_high_res);
+        self.bits[..pixel_count].fill(0);
+        for (plane, plane_data) in planes.chunks(plane_len).enumerate() {
+            for index in 0..pixel_count {
+                let bit = (plane_data[index / 8] >> (7 - (index % 8))) & 1;
+                self.bits[index] |= bit << plane;
+            }
+        }
+        self.is_dirty = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_high_res() {
+        let mut display = Display::new();
+        assert_eq!((display.width(), display.height()), (64, 32));
+        assert!(!display.render_sprite(100, 50, &[0x80], SPRITE_WIDTH, false, false));
+        assert!(!display.bits.contains(&1));
+
+        display.set_pixel(0, 0, true);
+        display.set_high_res(true);
+        assert_eq!((display.width(), display.height()), (128, 64));
+        assert!(display.bits[0] == 0, "Switching resolution should clear the screen");
+
+        assert!(!display.render_sprite(100, 50, &[0x80], SPRITE_WIDTH, false, false));
+        assert!(display.bits[128 * 50 + 100] == 1);
+        assert!(display.render_sprite(100, 50, &[0x80], SPRITE_WIDTH, false, false));
+    }
+
+    #[test]
+    pub fn test_custom_resolution() {
+        let mut display = Display::with_resolution(64, 48);
+        assert_eq!((display.width(), display.height()), (64, 48));
+        assert_eq!(display.to_rgba().len(), 64 * 48 * 4);
+
+        // Rows past 32 are on screen, but rows past 48 are clipped or wrapped.
+        display.render_sprite(0, 40, &[0x80; 10], SPRITE_WIDTH, false, false);
+        assert_eq!(display.pixels().iter().filter(|&&pixel| pixel != 0).count(), 8);
+        assert!(display.render_sprite(0, 46, &[0x80; 4], SPRITE_WIDTH, true, true), "Wrapped rows should collide at the top");
+        assert_eq!(display.pixels()[64 * 47], 0);
+        assert_eq!(display.pixels()[0], 1);
+        assert_eq!(display.pixels()[64], 1);
+
+        display.set_high_res(true);
+        assert_eq!((display.width(), display.height()), (128, 96));
+        display.set_pixel(127, 95, true);
+        assert_eq!(display.to_ascii().lines().last().unwrap().trim_end(), format!("{:>128}", "#").trim_end());
+    }
+
+    #[test]
+    pub fn test_wrap_sprites() {
+        let mut display = Display::new();
+        assert!(!display.render_sprite(62, 31, &[0xFF, 0xFF], SPRITE_WIDTH, false, false));
+        assert!(display.bits[64 * 31 + 62] == 1 && display.bits[64 * 31 + 63] == 1);
+        assert_eq!(display.bits.iter().filter(|&&bit| bit != 0).count(), 2, "Pixels past the edges should be clipped");
+
+        display.clear();
+        display.set_pixel(0, 0, true);
+        assert!(display.render_sprite(62, 31, &[0xFF, 0xFF], SPRITE_WIDTH, true, true), "Wrapped pixels should collide");
+        assert!(display.bits[64 * 31 + 62] == 1 && display.bits[64 * 31 + 5] == 1);
+        assert!(display.bits[0] == 0, "Collided pixel should be erased");
+        assert!(display.bits[1] == 1 && display.bits[5] == 1);
+        assert!(display.bits[6] == 0);
+        assert_eq!(display.bits.iter().filter(|&&bit| bit != 0).count(), 15);
+    }
+
+    #[test]
+    pub fn test_wrap_axes() {
+        let lit_count = |display: &Display| display.bits.iter().filter(|&&bit| bit != 0).count();
+        let mut display = Display::new();
+        for (is_wrap_x, is_wrap_y, expected_count) in [(false, false, 2), (true, false, 8), (false, true, 4), (true, true, 16)] {
+            display.clear();
+            display.render_sprite(62, 31, &[0xFF, 0xFF], SPRITE_WIDTH, is_wrap_x, is_wrap_y);
+            assert_eq!(lit_count(&display), expected_count, "Wrapping x: {}, y: {}", is_wrap_x, is_wrap_y);
+            assert_eq!(display.bits[64 * 31] == 1, is_wrap_x, "The right edge should only wrap when x wraps");
+            assert_eq!(display.bits[62] == 1, is_wrap_y, "The bottom edge should only wrap when y wraps");
+            assert_eq!(display.bits[0] == 1, is_wrap_x && is_wrap_y);
+        }
+    }
+
+    #[test]
+    pub fn test_export() {
+        let mut display = Display::new();
+        display.render_sprite(1, 1, &[0b1010_0000], SPRITE_WIDTH, false, false);
+
+        let rgba = display.to_rgba();
+        assert_eq!(rgba.len(), 64 * 32 * 4);
+        assert_eq!(rgba[..4], [DARK_COLOR.0, DARK_COLOR.1, DARK_COLOR.2, 255]);
+        let lit = (64 + 1) * 4;
+        assert_eq!(rgba[lit..(lit + 4)], [LIGHT_COLOR.0, LIGHT_COLOR.1, LIGHT_COLOR.2, 255]);
+
+        let mut buffer = vec![0; rgba.len()];
+        display.draw(&mut buffer);
+        assert_eq!(buffer, rgba);
+
+        let ascii = display.to_ascii();
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert_eq!(lines[0], " ".repeat(64));
+        assert_eq!(lines[1], format!(" # #{}", " ".repeat(60)));
+    }
+
+    #[test]
+    pub fn test_serialize() {
+        let mut rng = fastrand::Rng::with_seed(78);
+        let mut display = Display::new();
+        for index in 0..(64 * 32) {
+            display.bits[index] = rng.u8(0..=1);
+        }
+        let data = display.serialize();
+        assert_eq!(data.len(), 1 + 64 * 32 / 8, "Plain CHIP-8 pixels should pack 8 to a byte");
+
+        let mut received = Display::new();
+        received.deserialize(&data).unwrap();
+        assert_eq!(received.pixels(), display.pixels());
+
+        display.set_high_res(true);
+        for index in 0..(128 * 64) {
+            display.bits[index] = rng.u8(0..=ALL_PLANES);
+        }
+        let data = display.serialize();
+        assert_eq!(data.len(), 1 + 2 * 128 * 64 / 8, "Both planes should be sent when the second is in use");
+        received.deserialize(&data).unwrap();
+        assert!(received.is_high_res());
+        assert_eq!(received.pixels(), display.pixels());
+
+        let mut smaller = Display::with_resolution(32, 16);
+        assert_eq!(smaller.deserialize(&data), Err(DisplayError::InvalidData { len: data.len() }));
+        assert_eq!(received.deserialize(&[]), Err(DisplayError::InvalidData { len: 0 }));
+        assert_eq!(received.deserialize(&[0x80]), Err(DisplayError::InvalidData { len: 1 }));
+    }
+
+    #[test]
+    pub fn test_draw_if_dirty() {
+        let mut display = Display::new();
+        let mut buffer = vec![0; 64 * 32 * 4];
+        assert!(display.draw_if_dirty(&mut buffer), "The first draw should always happen");
+        assert!(!display.draw_if_dirty(&mut buffer));
+
+        display.render_sprite(0, 0, &[], SPRITE_WIDTH, false, false);
+        display.end_frame();
+        assert!(!display.draw_if_dirty(&mut buffer), "Drawing nothing shouldn't dirty the display");
+
+        display.render_sprite(0, 0, &[0x80], SPRITE_WIDTH, false, false);
+        assert!(display.draw_if_dirty(&mut buffer));
+        assert_eq!(buffer[..4], [LIGHT_COLOR.0, LIGHT_COLOR.1, LIGHT_COLOR.2, 255]);
+        assert!(!display.draw_if_dirty(&mut buffer));
+
+        display.clear();
+        assert!(display.draw_if_dirty(&mut buffer));
+        display.set_inverted(true);
+        assert!(display.draw_if_dirty(&mut buffer), "Changing the colors should dirty the display");
+
+        display.set_persistence(2);
+        display.render_sprite(0, 0, &[0x80], SPRITE_WIDTH, false, false);
+        display.end_frame();
+        display.clear();
+        display.draw_if_dirty(&mut buffer);
+        display.end_frame();
+        assert!(display.draw_if_dirty(&mut buffer), "Fading pixels should dirty the display");
+        display.end_frame();
+        display.end_frame();
+        display.draw_if_dirty(&mut buffer);
+        display.end_frame();
+        assert!(!display.draw_if_dirty(&mut buffer), "Faded pixels shouldn't keep the display dirty");
+    }
+
+    #[test]
+    pub fn test_dirty_since() {
+        let mut display = Display::new();
+        display.render_sprite(2, 3, &[0b1000_0000], SPRITE_WIDTH, false, false);
+        let previous = display.pixels().to_vec();
+        assert!(display.dirty_since(&previous).is_empty());
+
+        display.render_sprite(2, 3, &[0b1100_0000], SPRITE_WIDTH, false, false);
+        assert_eq!(display.dirty_since(&previous), vec![(2, 3), (3, 3)]);
+
+        display.set_high_res(true);
+        assert_eq!(display.dirty_since(&previous).len(), 128 * 64, "A resolution change should dirty every pixel");
+    }
+
+    #[test]
+    pub fn test_set_colors() {
+        let mut display = Display::new();
+        display.set_pixel(0, 0, true);
+        display.set_colors((0xAA, 0xBB, 0xCC), (0x11, 0x22, 0x33));
+
+        let mut buffer = vec![0; 64 * 32 * 4];
+        display.draw(&mut buffer);
+        assert_eq!(buffer[..8], [0xAA, 0xBB, 0xCC, 255, 0x11, 0x22, 0x33, 255]);
+    }
+
+    #[test]
+    pub fn test_inverted() {
+        let mut display = Display::new();
+        display.set_pixel(0, 0, true);
+        display.set_colors((0xAA, 0xBB, 0xCC), (0x11, 0x22, 0x33));
+        display.set_inverted(true);
+        assert_eq!(display.to_rgba()[..8], [0x11, 0x22, 0x33, 255, 0xAA, 0xBB, 0xCC, 255]);
+        assert_eq!(display.pixels()[..2], [1, 0], "Inverting shouldn't change the pixels themselves");
+
+        display.set_inverted(false);
+        assert_eq!(display.to_rgba()[..8], [0xAA, 0xBB, 0xCC, 255, 0x11, 0x22, 0x33, 255]);
+    }
+
+    #[test]
+    pub fn test_brightness() {
+        let mut display = Display::new();
+        display.set_pixel(0, 0, true);
+        display.set_colors((200, 100, 50), (20, 10, 0));
+        display.set_brightness(0.5);
+        assert_eq!(display.to_rgba()[..8], [100, 50, 25, 255, 10, 5, 0, 255]);
+
+        display.set_brightness(2.0);
+        assert_eq!(display.brightness(), 1.0);
+        assert_eq!(display.to_rgba()[..4], [200, 100, 50, 255]);
+    }
+
+    #[test]
+    pub fn test_render_sprite_counted() {
+        let mut display = Display::new();
+        assert_eq!(display.render_sprite_counted(0, 0, &[0xF0, 0x0F], SPRITE_WIDTH, false, false), 0);
+        // Overlaps two pixels of the first row and one of the second.
+        assert_eq!(display.render_sprite_counted(2, 0, &[0xFF, 0x20], SPRITE_WIDTH, false, false), 3);
+        assert_eq!(display.render_sprite_counted(10, 0, &[0xFF], SPRITE_WIDTH, false, false), 0);
+
+        display.set_plane_mask(ALL_PLANES);
+        display.clear();
+        display.render_sprite(0, 0, &[0x80, 0x80], SPRITE_WIDTH, false, false);
+        assert_eq!(display.render_sprite_counted(0, 0, &[0xC0, 0x80], SPRITE_WIDTH, false, false), 2, "Each plane should count separately");
+        assert!(display.render_sprite(0, 0, &[0x40, 0x00], SPRITE_WIDTH, false, false), "Any erased pixel should count as a collision");
+    }
+
+    #[test]
+    pub fn test_scroll_down() {
+        let mut display = Display::new();
+        display.set_pixel(3, 0, true);
+        display.set_pixel(5, 30, true);
+
+        display.scroll_down(2);
+        assert!(display.bits[64 * 2 + 3] == 1);
+        assert!(display.bits[3] == 0);
+        assert!(!display.bits[..(64 * 2)].contains(&1));
+        assert!(!display.bits[(64 * 30)..(64 * 32)].contains(&1), "Pixels on the bottom edge should be dropped");
+    }
+
+    #[test]
+    pub fn test_scroll_up() {
+        let mut display = Display::new();
+        display.set_pixel(3, 1, true);
+        display.set_pixel(5, 31, true);
+
+        display.scroll_up(1);
+        assert!(display.bits[3] == 1);
+        assert!(display.bits[64 * 30 + 5] == 1);
+        assert!(!display.bits[(64 * 31)..(64 * 32)].contains(&1));
+
+        display.scroll_up(1);
+        assert!(display.bits[64 * 29 + 5] == 1);
+        assert_eq!(display.bits.iter().filter(|&&bit| bit != 0).count(), 1, "Pixels on the top edge should be dropped");
+    }
+
+    #[test]
+    pub fn test_large_sprite() {
+        let mut display = Display::new();
+        display.set_high_res(true);
+        let mut sprite = [0u8; 32];
+        sprite[0] = 0x80;
+        sprite[1] = 0x01;
+        sprite[31] = 0xFF;
+
+        assert!(!display.render_sprite(10, 20, &sprite, LARGE_SPRITE_WIDTH, false, false));
+        assert!(display.bits[128 * 20 + 10] == 1);
+        assert!(display.bits[128 * 20 + 25] == 1);
+        assert!(display.bits[(128 * 35 + 18)..(128 * 35 + 26)].iter().all(|&bit| bit == 1));
+        assert_eq!(display.bits.iter().filter(|&&bit| bit != 0).count(), 10);
+
+        assert!(display.render_sprite(10, 20, &sprite, LARGE_SPRITE_WIDTH, false, false));
+        assert!(!display.bits.contains(&1));
+    }
+
+    #[test]
+    pub fn test_sprite_widths() {
+        let mut display = Display::new();
+        assert!(!display.render_sprite(0, 0, &[0b1000_0001, 0xAA], SPRITE_WIDTH, false, false));
+        assert_eq!(display.to_ascii().lines().next(), Some(format!("{:64}", "#      #").as_str()));
+        assert_eq!(display.bits.iter().filter(|&&bit| bit != 0).count(), 2 + 4);
+
+        // Only the second byte of each row overlaps the first sprite, so the collision has to be
+        // picked up from past the first 8 pixels.
+        display.clear();
+        assert!(!display.render_sprite(8, 0, &[0x01], SPRITE_WIDTH, false, false));
+        assert!(display.render_sprite(0, 0, &[0x00, 0x01, 0x00, 0x00], LARGE_SPRITE_WIDTH, false, false));
+        assert!(display.bits[15] == 0);
+        assert!(!display.render_sprite(0, 0, &[0x80, 0x01, 0x00, 0x00], LARGE_SPRITE_WIDTH, false, false));
+        assert!(display.bits[0] == 1 && display.bits[15] == 1);
+        assert_eq!(display.bits.iter().filter(|&&bit| bit != 0).count(), 2);
+    }
+
+    #[test]
+    pub fn test_scroll_horizontal() {
+        let mut display = Display::new();
+        display.set_pixel(0, 1, true);
+        display.set_pixel(62, 1, true);
+
+        display.scroll_right();
+        assert!(display.bits[64 + 4] == 1);
+        assert!(!display.bits[(64 * 2)..].contains(&1), "Pixels should not spill onto the next row");
+        assert_eq!(display.bits.iter().filter(|&&bit| bit != 0).count(), 1);
+
+        display.scroll_left();
+        assert!(display.bits[64] == 1);
+        assert_eq!(display.bits.iter().filter(|&&bit| bit != 0).count(), 1);
+
+        display.scroll_left();
+        assert!(!display.bits.contains(&1), "Pixels on the left edge should be dropped");
+    }
+
+    #[test]
+    pub fn test_planes() {
+        let mut display = Display::new();
+        display.set_plane_mask(0b11);
+        assert_eq!(display.plane_count(), 2);
+
+        // The first byte is drawn to plane 1, and the second to plane 2.
+        assert!(!display.render_sprite(0, 0, &[0b1100_0000, 0b1010_0000], SPRITE_WIDTH, false, false));
+        assert_eq!(display.pixels()[..4], [3, 1, 2, 0]);
+
+        display.set_palette([(0, 0, 0), (1, 1, 1), (2, 2, 2), (3, 3, 3)]);
+        let mut buffer = vec![0; 64 * 32 * 4];
+        display.draw(&mut buffer);
+        assert_eq!(buffer[..16], [3, 3, 3, 255, 1, 1, 1, 255, 2, 2, 2, 255, 0, 0, 0, 255]);
+        assert_eq!(display.to_ascii().lines().next().unwrap()[..4], *"### ");
+
+        // Only the selected planes are affected by drawing, scrolling and clearing.
+        display.set_plane_mask(0b10);
+        assert!(display.render_sprite(0, 0, &[0b1000_0000], SPRITE_WIDTH, false, false));
+        assert_eq!(display.pixels()[..4], [1, 1, 2, 0]);
+        display.scroll_right();
+        assert_eq!(display.pixels()[..8], [1, 1, 0, 0, 0, 0, 2, 0]);
+        display.clear();
+        assert_eq!(display.pixels()[..8], [1, 1, 0, 0, 0, 0, 0, 0]);
+
+        display.set_plane_mask(0);
+        assert!(!display.render_sprite(0, 0, &[0xFF], SPRITE_WIDTH, false, false));
+        assert_eq!(display.pixels()[..4], [1, 1, 0, 0]);
+    }
+
+    #[test]
+    pub fn test_persistence() {
+        let mut display = Display::new();
+        display.set_colors((255, 255, 255), (0, 0, 0));
+        display.set_persistence(2);
+        display.set_pixel(0, 0, true);
+        display.end_frame();
+        assert_eq!(display.to_rgba()[..4], [255, 255, 255, 255]);
+
+        display.set_pixel(0, 0, true);
+        assert_eq!(display.to_rgba()[..4], [170, 170, 170, 255], "Pixels should start fading as soon as they turn off");
+        display.end_frame();
+        assert_eq!(display.to_rgba()[..4], [170, 170, 170, 255]);
+        display.end_frame();
+        assert_eq!(display.to_rgba()[..4], [85, 85, 85, 255]);
+        display.end_frame();
+        assert_eq!(display.to_rgba()[..4], [0, 0, 0, 255], "Pixels should be unlit once the persistence has passed");
+
+        display.set_persistence(0);
+        display.set_pixel(0, 0, true);
+        display.end_frame();
+        display.set_pixel(0, 0, true);
+        assert_eq!(display.to_rgba()[..4], [0, 0, 0, 255], "Pixels shouldn't linger without persistence");
     }
 }
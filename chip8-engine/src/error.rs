@@ -0,0 +1,94 @@
+use core::fmt;
+use crate::display::DisplayError;
+use crate::heap::{self, HeapError};
+use crate::stack::StackError;
+
+/// Errors raised while loading a program, or which halt the machine when raised during a `try_tick`.
+/// Executing instructions never panics, so a misbehaving program surfaces as one of these instead.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MachineError {
+    /// A subroutine was called while the stack was already full
+    StackOverflow,
+
+    /// A subroutine returned while the stack was empty
+    StackUnderflow,
+
+    /// Memory was accessed past its end, starting at `address`
+    MemoryOutOfBounds { address: usize },
+
+    /// The instruction at `pc` couldn't be decoded
+    UnknownOpcode { opcode: u16, pc: usize },
+
+    /// A machine code routine at `address` was called from `pc`, which only the original hardware could run
+    SysCall { address: u16, pc: usize },
+
+    /// The program counter reached a `0x0000` opcode at `pc`, which usually means the program ran past its end
+    ZeroOpcode { pc: usize },
+
+    /// The program is `overflow` bytes too large to be loaded into memory
+    ProgramTooLarge { overflow: usize },
+
+    /// The program was to be loaded at `address`, which is past the end of memory
+    InvalidLoadAddress { address: usize },
+
+    /// A replacement font was `len` bytes long, rather than 5 bytes for each of the 16 hex digits
+    InvalidFontLength { len: usize },
+
+    /// A serialized display was `len` bytes long, or had an unknown header, so it doesn't match the display's resolution
+    InvalidDisplayData { len: usize },
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineError::StackOverflow => write!(f, "Max stack size reached"),
+            MachineError::StackUnderflow => write!(f, "Attempt to return from a subroutine with an empty stack"),
+            MachineError::MemoryOutOfBounds { address } =>
+                write!(f, "Attempt to access memory out of bounds at address {:#06x}", address),
+            MachineError::UnknownOpcode { opcode, pc } =>
+                write!(f, "Unknown opcode {:#06x} at address {:#05x}", opcode, pc),
+            MachineError::SysCall { address, pc } =>
+                write!(f, "Call to machine code routine {:#05x} at address {:#05x}, which can't be emulated", address, pc),
+            MachineError::ZeroOpcode { pc } =>
+                write!(f, "Reached opcode 0x0000 at address {:#05x}, past the end of the program", pc),
+            MachineError::ProgramTooLarge { overflow } =>
+                write!(f, "Program is {} bytes too large to fit in memory", overflow),
+            MachineError::InvalidLoadAddress { address } =>
+                write!(f, "Program can't be loaded at address {:#06x}, which is past the end of memory", address),
+            MachineError::InvalidFontLength { len } =>
+                write!(f, "Font is {} bytes long, but must be {} bytes (5 for each hex digit)", len, heap::SIGILS_LENGTH),
+            MachineError::InvalidDisplayData { len } =>
+                write!(f, "Display data of {} bytes doesn't match the display's resolution", len),
+        }
+    }
+}
+
+impl core::error::Error for MachineError {}
+
+impl From<StackError> for MachineError {
+    fn from(error: StackError) -> Self {
+        match error {
+            StackError::Overflow => MachineError::StackOverflow,
+            StackError::Underflow => MachineError::StackUnderflow,
+        }
+    }
+}
+
+impl From<HeapError> for MachineError {
+    fn from(error: HeapError) -> Self {
+        match error {
+            HeapError::OutOfBounds { address } => MachineError::MemoryOutOfBounds { address },
+            HeapError::ProgramTooLarge { overflow } => MachineError::ProgramTooLarge { overflow },
+            HeapError::InvalidLoadAddress { address } => MachineError::InvalidLoadAddress { address },
+            HeapError::InvalidFontLength { len } => MachineError::InvalidFontLength { len },
+        }
+    }
+}
+
+impl From<DisplayError> for MachineError {
+    fn from(error: DisplayError) -> Self {
+        match error {
+            DisplayError::InvalidData { len } => MachineError::InvalidDisplayData { len },
+        }
+    }
+}
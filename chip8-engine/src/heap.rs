@@ -1,8 +1,14 @@
+use alloc::vec;
+use alloc::vec::Vec;
 
-const MEMORY_SIZE: usize = 4096;
-const SIGILS_LENGTH: usize = 80;
+pub const MEMORY_SIZE: usize = 4096;
+/// The font holds a 5 byte sprite for each of the 16 hex digits.
+pub const SIGILS_LENGTH: usize = 80;
 
 pub const OFFSET_FONT: usize = 0x050;
+/// The SUPER-CHIP big font sits right after the small one, and holds a 10 byte sprite for each hex digit.
+pub const OFFSET_BIG_FONT: usize = OFFSET_FONT + SIGILS_LENGTH;
+const BIG_SIGILS_LENGTH: usize = 160;
 pub const OFFSET_DATA: usize = 0x200;
 
 const FONT_SIGILS: [u8; SIGILS_LENGTH] = [
@@ -24,52 +30,161 @@ const FONT_SIGILS: [u8; SIGILS_LENGTH] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+const BIG_FONT_SIGILS: [u8; BIG_SIGILS_LENGTH] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HeapError {
+    /// `address` is the first address past the end of memory which was accessed
+    OutOfBounds { address: usize },
+
+    /// The program is `overflow` bytes larger than the memory available after its load address
+    ProgramTooLarge { overflow: usize },
+
+    /// The program was to be loaded at `address`, which is past the end of memory
+    InvalidLoadAddress { address: usize },
+
+    /// A font of `len` bytes was given, rather than `SIGILS_LENGTH`
+    InvalidFontLength { len: usize },
+}
+
+/// The machine's memory, holding the fonts and the program.
+///
+/// Programs are free to write over their own code, so every write goes through `set_bytes`.
+/// Anything which keeps a copy of memory around, like a cache of decoded instructions,
+/// must be kept up to date from there, or self-modifying programs will run stale code.
 pub struct Heap {
-    elements: [u8; MEMORY_SIZE]
+    elements: Vec<u8>
 }
 
 impl Heap {
-    pub fn new(program_bytes: Vec<u8>) -> Self {
-        let mut elements: [u8; MEMORY_SIZE] = [0; MEMORY_SIZE];
-
-        for i in 0..SIGILS_LENGTH {
-            elements[OFFSET_FONT + i] = FONT_SIGILS[i];
+    /// Copies the program into memory at `load_address`, which is usually `OFFSET_DATA`.
+    pub fn new(program_bytes: &[u8], memory_size: usize, load_address: usize) -> Result<Self, HeapError> {
+        assert!(memory_size >= OFFSET_DATA, "Memory must be large enough to hold the font and program start");
+        if load_address >= memory_size {
+            return Err(HeapError::InvalidLoadAddress { address: load_address });
         }
-
-        for i in 0..program_bytes.len() {
-            elements[OFFSET_DATA + i] = program_bytes[i];
+        let capacity = memory_size - load_address;
+        if program_bytes.len() > capacity {
+            return Err(HeapError::ProgramTooLarge { overflow: program_bytes.len() - capacity });
         }
+        let mut elements = vec![0; memory_size];
 
-        Heap { elements }
-    }
+        elements[OFFSET_FONT..(OFFSET_FONT + SIGILS_LENGTH)].copy_from_slice(&FONT_SIGILS);
+        elements[OFFSET_BIG_FONT..(OFFSET_BIG_FONT + BIG_SIGILS_LENGTH)].copy_from_slice(&BIG_FONT_SIGILS);
+        elements[load_address..(load_address + program_bytes.len())].copy_from_slice(program_bytes);
 
-    pub fn set_byte(&mut self, index: usize, value: u8) {
-        assert!(index < MEMORY_SIZE, "Attempt to set byte outside of memory");
-        self.elements[index] = value;
+        Ok(Heap { elements })
     }
 
-    pub fn set_bytes(&mut self, index: usize, values: &[u8]) {
-        for i in 0..values.len() {
-            self.set_byte(index + i, values[i]);
+    /// Replaces the font at `OFFSET_FONT`, which must hold a 5 byte sprite for each hex digit.
+    pub fn set_font(&mut self, font: &[u8]) -> Result<(), HeapError> {
+        if font.len() != SIGILS_LENGTH {
+            return Err(HeapError::InvalidFontLength { len: font.len() });
         }
+        self.set_bytes(OFFSET_FONT, font)
+    }
+
+    #[cfg(test)]
+    pub fn set_byte(&mut self, index: usize, value: u8) -> Result<(), HeapError> {
+        self.set_bytes(index, &[value])
+    }
+
+    /// Nothing is written if any of the bytes would land past the end of memory.
+    /// This is the only place memory is changed after it's created.
+    pub fn set_bytes(&mut self, index: usize, values: &[u8]) -> Result<(), HeapError> {
+        self.check_bounds(index, values.len())?;
+        self.elements[index..(index + values.len())].copy_from_slice(values);
+        Ok(())
     }
 
-    pub fn set_as_decimal(&mut self, index: usize, value: u8) {
-        self.set_byte(index + 0, value / 100);
-        self.set_byte(index + 1, (value / 10) % 10);
-        self.set_byte(index + 2, (value % 100) % 10);
+    pub fn set_as_decimal(&mut self, index: usize, value: u8) -> Result<(), HeapError> {
+        self.set_bytes(index, &[value / 100, (value / 10) % 10, (value % 100) % 10])
     }
 
+    /// Returns the `len` bytes starting at `index`, mirroring `set_bytes`.
     pub fn get_bytes(&self, index: usize, len: usize) -> &[u8] {
-        &self.elements[index..=(index + len)]
+        &self.elements[index..(index + len)]
     }
 
     pub fn get_all_bytes(&self) -> &[u8] {
         &self.elements[..]
     }
 
-    pub fn get_sprite(&self, index: usize, sprite_height: u8) -> &[u8] {
-        let end = index + sprite_height as usize;
-        &self.elements[index..end]
+    pub fn get_sprite(&self, index: usize, sprite_height: u8) -> Result<&[u8], HeapError> {
+        self.check_bounds(index, sprite_height as usize)?;
+        Ok(self.get_bytes(index, sprite_height as usize))
+    }
+
+    /// Checks that the `len` bytes starting at `index` all lie within memory.
+    pub fn check_bounds(&self, index: usize, len: usize) -> Result<(), HeapError> {
+        index
+            .checked_add(len)
+            .filter(|&end| end <= self.elements.len())
+            .map(|_| ())
+            .ok_or(HeapError::OutOfBounds { address: index.max(self.elements.len()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_large_memory() {
+        let program_bytes: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+        let mut heap = Heap::new(&program_bytes, 0x10000, OFFSET_DATA).unwrap();
+        assert_eq!(heap.get_all_bytes().len(), 0x10000);
+        assert_eq!(heap.get_bytes(OFFSET_DATA + 4990, 2), &[(4990 % 256) as u8, (4991 % 256) as u8]);
+
+        heap.set_bytes(0xFFFD, &[0xAB, 0xCD, 0xEF]).unwrap();
+        assert_eq!(heap.get_bytes(0xFFFD, 3), &[0xAB, 0xCD, 0xEF]);
+        assert_eq!(heap.set_bytes(0xFFFE, &[0x12, 0x34, 0x56]), Err(HeapError::OutOfBounds { address: 0x10000 }));
+        assert_eq!(heap.get_bytes(0xFFFE, 2), &[0xCD, 0xEF], "Nothing should be written when out of bounds");
+    }
+
+    #[test]
+    pub fn test_program_too_large() {
+        assert!(Heap::new(&[0; MEMORY_SIZE - OFFSET_DATA], MEMORY_SIZE, OFFSET_DATA).is_ok());
+        assert_eq!(Heap::new(&[0; 4000], MEMORY_SIZE, OFFSET_DATA).err(), Some(HeapError::ProgramTooLarge { overflow: 416 }));
+        assert_eq!(Heap::new(&[0; 3000], MEMORY_SIZE, 0x600).err(), Some(HeapError::ProgramTooLarge { overflow: 440 }));
+        assert_eq!(Heap::new(&[], MEMORY_SIZE, MEMORY_SIZE).err(), Some(HeapError::InvalidLoadAddress { address: MEMORY_SIZE }));
+    }
+
+    #[test]
+    pub fn test_get_sprite_bounds() {
+        let heap = Heap::new(&[], MEMORY_SIZE, OFFSET_DATA).unwrap();
+        assert_eq!(heap.get_sprite(OFFSET_FONT, 5), Ok(&FONT_SIGILS[..5]));
+        assert_eq!(heap.get_sprite(OFFSET_BIG_FONT, 10), Ok(&BIG_FONT_SIGILS[..10]));
+        assert_eq!(heap.get_sprite(MEMORY_SIZE - 5, 5).map(|sprite| sprite.len()), Ok(5));
+        assert_eq!(heap.get_sprite(MEMORY_SIZE - 5, 15), Err(HeapError::OutOfBounds { address: MEMORY_SIZE }));
+        assert_eq!(heap.get_sprite(MEMORY_SIZE + 5, 1), Err(HeapError::OutOfBounds { address: MEMORY_SIZE + 5 }));
+    }
+
+    #[test]
+    pub fn test_set_font() {
+        let mut heap = Heap::new(&[], MEMORY_SIZE, OFFSET_DATA).unwrap();
+        let font: Vec<u8> = (0..SIGILS_LENGTH as u8).collect();
+        heap.set_font(&font).unwrap();
+        assert_eq!(heap.get_bytes(OFFSET_FONT, SIGILS_LENGTH), &font[..]);
+
+        assert_eq!(heap.set_font(&FONT_SIGILS[..75]), Err(HeapError::InvalidFontLength { len: 75 }));
+        assert_eq!(heap.get_bytes(OFFSET_FONT, SIGILS_LENGTH), &font[..], "An invalid font shouldn't be installed");
     }
 }
@@ -1,3 +1,4 @@
+use core::fmt;
 use crate::instruction::Instruction::*;
 use crate::registers::Register;
 use crate::word::Word;
@@ -10,6 +11,7 @@ pub enum Instruction {
     Unimplemented { opcode: u16 },
 
     /// Indicates an end to the program execution
+    /// Decoded from `0x0000`, which is uninitialized memory, and the non-standard `0x00DE`
     EndProgram,
 
     /// Clear the screen
@@ -18,6 +20,32 @@ pub enum Instruction {
     /// Return from a subroutine
     ReturnSubroutine,
 
+    /// Scroll the display down by `N` pixels (SUPER-CHIP)
+    ScrollDown { amount: u8 },
+
+    /// Scroll the display up by `N` pixels (XO-CHIP)
+    /// `0x00DE` is still treated as `EndProgram`, so scrolling up by 14 pixels isn't supported
+    ScrollUp { amount: u8 },
+
+    /// Scroll the display right by 4 pixels (SUPER-CHIP)
+    ScrollRight,
+
+    /// Scroll the display left by 4 pixels (SUPER-CHIP)
+    ScrollLeft,
+
+    /// Switch to the 64x32 low resolution display mode (SUPER-CHIP)
+    DisableHighRes,
+
+    /// Switch to the 128x64 high resolution display mode (SUPER-CHIP)
+    EnableHighRes,
+
+    /// Select the display planes `N` which are drawn to, cleared and scrolled (XO-CHIP)
+    SelectPlanes { mask: u8 },
+
+    /// Execute the machine code routine at address `NNN`, which interpreters other than the original
+    /// COSMAC VIP's treat as a no-op
+    SysCall { address: u16 },
+
     /// Jump to address `NNN`
     Goto { address: u16 },
 
@@ -82,6 +110,10 @@ pub enum Instruction {
     /// Store memory address `NNN` in register `I`
     IStoreAddress { address: u16 },
 
+    /// Store the 16-bit memory address `NNNN` from the following word in register `I` (XO-CHIP)
+    /// Decoded from `0xF000`, this is the only instruction which takes up two words
+    IStoreLongAddress { address: u16 },
+
     /// Jump to address `NNN + V0`
     GotoOffsetted { address: u16 },
 
@@ -89,6 +121,7 @@ pub enum Instruction {
     RegisterStoreRandom { register: Register, mask: u8 },
 
     /// Draw a sprite at position `VX`, `VY` with `N` bytes of sprite data starting at the address stored in `I`
+    /// When `N` is 0, a 16x16 sprite is drawn from 32 bytes of sprite data instead (SUPER-CHIP / XO-CHIP)
     /// Set `VF` to 01 if any set pixels are changed to unset, and 00 otherwise
     DrawSprite { register_x: Register, register_y: Register, sprite_height: u8 },
 
@@ -98,6 +131,9 @@ pub enum Instruction {
     /// Skip the following instruction if the key corresponding to the hex value currently stored in register `VX` is not pressed
     SkipIfKeyOff { register: Register },
 
+    /// Load the 16 byte audio pattern buffer from the memory starting at the address stored in `I` (XO-CHIP)
+    LoadAudioPattern,
+
     /// Store the current value of the delay timer in register `VX`
     DelayTimerToRegister { register: Register },
 
@@ -110,12 +146,18 @@ pub enum Instruction {
     /// Set the sound timer to the value of register `VX`
     RegisterToSoundTimer { register: Register },
 
+    /// Set the playback pitch of the audio pattern to the value of register `VX` (XO-CHIP)
+    SetAudioPitch { register: Register },
+
     /// Add the value stored in register `VX` to register `I`
     IAddOffset { register: Register },
 
     /// Set `I` to the memory address of the sprite data corresponding to the hexadecimal digit stored in register `VX`
     IStoreDigitAddress { register: Register },
 
+    /// Set `I` to the memory address of the 10 byte tall sprite for the hexadecimal digit stored in register `VX` (SUPER-CHIP)
+    IStoreBigDigitAddress { register: Register },
+
     /// Store the binary-coded decimal equivalent of the value stored in register `VX` at addresses `I`, `I + 1`, and `I + 2`
     /// See also: https://en.wikipedia.org/wiki/Binary-coded_decimal
     HexToDecimal { register: Register },
@@ -127,25 +169,47 @@ pub enum Instruction {
     /// Fill registers `V0` to `VX` inclusive with the values stored in memory starting at address `I`
     /// `I` is set to `I + X + 1` after operation
     RegistersLoad { max_register: Register },
+
+    /// Store the values of registers `V0` to `VX` inclusive in the RPL user flags (SUPER-CHIP)
+    /// Only `V0` to `V7` have a flag to be saved to
+    SaveFlags { max_register: Register },
+
+    /// Fill registers `V0` to `VX` inclusive with the values stored in the RPL user flags (SUPER-CHIP)
+    LoadFlags { max_register: Register },
 }
 
 impl Instruction {
+    /// Decodes the instruction at `pc`. `IStoreLongAddress` also reads the word after it, and can't be decoded
+    /// without it, so a `0xF000` at the end of `memory` is `Unimplemented`.
+    /// Panics if `memory` ends before the two bytes at `pc`, see `try_decode`.
     pub fn new(memory: &[u8], pc: usize) -> Self {
-        assert!(pc + 1 < memory.len(), "Expecting two free bytes at pc location");
+        Self::try_decode(memory, pc).expect("Expecting two free bytes at pc location")
+    }
 
-        let word = Word::new(memory, pc);
-        match word.c() {
+    /// Decodes the instruction at `pc` like `new`, or returns `None` if `memory` ends before the two bytes at `pc`.
+    pub fn try_decode(memory: &[u8], pc: usize) -> Option<Self> {
+        let word = Word::try_new(memory, pc)?;
+        let instruction = match word.c() {
             0x0 => match word.nnn() {
                 0x000 | 0x0DE => EndProgram,
                 0x0E0 => ClearScreen,
                 0x0EE => ReturnSubroutine,
-                _ => Unimplemented { opcode: word.0 },
+                0x0C0..=0x0CF => ScrollDown { amount: word.n() },
+                0x0D0..=0x0DF => ScrollUp { amount: word.n() },
+                0x0FB => ScrollRight,
+                0x0FC => ScrollLeft,
+                0x0FE => DisableHighRes,
+                0x0FF => EnableHighRes,
+                address => SysCall { address },
             },
             0x1 => Goto { address: word.nnn() },
             0x2 => CallSubroutine { address: word.nnn() },
             0x3 => SkipIfValueEq { register: word.x(), value: word.nn() },
             0x4 => SkipIfValueNe { register: word.x(), value: word.nn() },
-            0x5 => SkipIfRegistersEq { register_x: word.x(), register_y: word.y() },
+            0x5 => match word.n() {
+                0x0 => SkipIfRegistersEq { register_x: word.x(), register_y: word.y() },
+                _ => Unimplemented { opcode: word.0 },
+            },
             0x6 => RegisterValueStore { register: word.x(), value: word.nn() },
             0x7 => RegisterValueAdd { register: word.x(), value: word.nn() },
             0x8 => {
@@ -164,7 +228,10 @@ impl Instruction {
                     _ => Unimplemented { opcode: word.0 },
                 }
             }
-            0x9 => SkipIfRegistersNe { register_x: word.x(), register_y: word.y() },
+            0x9 => match word.n() {
+                0x0 => SkipIfRegistersNe { register_x: word.x(), register_y: word.y() },
+                _ => Unimplemented { opcode: word.0 },
+            },
             0xA => IStoreAddress { address: word.nnn() },
             0xB => GotoOffsetted { address: word.nnn() },
             0xC => RegisterStoreRandom { register: word.x(), mask: word.nn() },
@@ -177,20 +244,216 @@ impl Instruction {
             0xF => {
                 let register = word.x();
                 match word.nn() {
+                    0x00 if register.idx() == 0 => match memory.get(pc + 2..pc + 4) {
+                        Some(&[high, low]) => IStoreLongAddress { address: u16::from_be_bytes([high, low]) },
+                        _ => Unimplemented { opcode: word.0 },
+                    },
+                    0x01 => SelectPlanes { mask: register.idx() as u8 },
+                    0x02 if register.idx() == 0 => LoadAudioPattern,
                     0x07 => DelayTimerToRegister { register },
                     0x0A => WaitForAnyKey { register },
                     0x15 => RegisterToDelayTimer { register },
                     0x18 => RegisterToSoundTimer { register },
                     0x1E => IAddOffset { register },
+                    0x3A => SetAudioPitch { register },
                     0x29 => IStoreDigitAddress { register },
+                    0x30 => IStoreBigDigitAddress { register },
                     0x33 => HexToDecimal { register },
                     0x55 => RegistersDump { max_register: register },
                     0x65 => RegistersLoad { max_register: register },
+                    0x75 => SaveFlags { max_register: register },
+                    0x85 => LoadFlags { max_register: register },
                     _ => Unimplemented { opcode: word.0 },
                 }
             },
 
             _ => panic!("Unreachable code")
+        };
+        Some(instruction)
+    }
+
+    /// The number of bytes the instruction takes up in memory.
+    pub fn size(&self) -> usize {
+        match self {
+            IStoreLongAddress { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    /// The name of the instruction's variant, which is useful for grouping instructions
+    /// regardless of their operands.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Unimplemented { .. } => "Unimplemented",
+            EndProgram => "EndProgram",
+            ClearScreen => "ClearScreen",
+            ReturnSubroutine => "ReturnSubroutine",
+            ScrollDown { .. } => "ScrollDown",
+            ScrollUp { .. } => "ScrollUp",
+            ScrollRight => "ScrollRight",
+            ScrollLeft => "ScrollLeft",
+            DisableHighRes => "DisableHighRes",
+            EnableHighRes => "EnableHighRes",
+            SelectPlanes { .. } => "SelectPlanes",
+            SysCall { .. } => "SysCall",
+            Goto { .. } => "Goto",
+            CallSubroutine { .. } => "CallSubroutine",
+            SkipIfValueEq { .. } => "SkipIfValueEq",
+            SkipIfValueNe { .. } => "SkipIfValueNe",
+            SkipIfRegistersEq { .. } => "SkipIfRegistersEq",
+            RegisterValueStore { .. } => "RegisterValueStore",
+            RegisterValueAdd { .. } => "RegisterValueAdd",
+            RegistersCopy { .. } => "RegistersCopy",
+            RegistersOrEq { .. } => "RegistersOrEq",
+            RegistersAndEq { .. } => "RegistersAndEq",
+            RegistersXorEq { .. } => "RegistersXorEq",
+            RegistersAdd { .. } => "RegistersAdd",
+            RegistersSub { .. } => "RegistersSub",
+            RegistersShiftRightEq { .. } => "RegistersShiftRightEq",
+            RegistersSubReversed { .. } => "RegistersSubReversed",
+            RegistersShiftLeftEq { .. } => "RegistersShiftLeftEq",
+            SkipIfRegistersNe { .. } => "SkipIfRegistersNe",
+            IStoreAddress { .. } => "IStoreAddress",
+            IStoreLongAddress { .. } => "IStoreLongAddress",
+            GotoOffsetted { .. } => "GotoOffsetted",
+            RegisterStoreRandom { .. } => "RegisterStoreRandom",
+            DrawSprite { .. } => "DrawSprite",
+            SkipIfKeyOn { .. } => "SkipIfKeyOn",
+            SkipIfKeyOff { .. } => "SkipIfKeyOff",
+            LoadAudioPattern => "LoadAudioPattern",
+            DelayTimerToRegister { .. } => "DelayTimerToRegister",
+            WaitForAnyKey { .. } => "WaitForAnyKey",
+            RegisterToDelayTimer { .. } => "RegisterToDelayTimer",
+            RegisterToSoundTimer { .. } => "RegisterToSoundTimer",
+            SetAudioPitch { .. } => "SetAudioPitch",
+            IAddOffset { .. } => "IAddOffset",
+            IStoreDigitAddress { .. } => "IStoreDigitAddress",
+            IStoreBigDigitAddress { .. } => "IStoreBigDigitAddress",
+            HexToDecimal { .. } => "HexToDecimal",
+            RegistersDump { .. } => "RegistersDump",
+            RegistersLoad { .. } => "RegistersLoad",
+            SaveFlags { .. } => "SaveFlags",
+            LoadFlags { .. } => "LoadFlags",
+        }
+    }
+
+    /// Encodes the instruction back into its opcode word, the inverse of `new`.
+    /// Returns `None` for `Unimplemented` and `EndProgram`, which don't correspond to a single opcode,
+    /// and for `IStoreLongAddress`, which takes up two words.
+    pub fn encode(&self) -> Option<u16> {
+        let x = |register: &Register| (register.idx() as u16) << 8;
+        let y = |register: &Register| (register.idx() as u16) << 4;
+        let opcode = match self {
+            Unimplemented { .. } | EndProgram | IStoreLongAddress { .. } => return None,
+            ClearScreen => 0x00E0,
+            ReturnSubroutine => 0x00EE,
+            ScrollDown { amount } => 0x00C0 | *amount as u16,
+            ScrollUp { amount } => 0x00D0 | *amount as u16,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            DisableHighRes => 0x00FE,
+            EnableHighRes => 0x00FF,
+            SelectPlanes { mask } => 0xF001 | (*mask as u16) << 8,
+            SysCall { address } => *address,
+            Goto { address } => 0x1000 | address,
+            CallSubroutine { address } => 0x2000 | address,
+            SkipIfValueEq { register, value } => 0x3000 | x(register) | *value as u16,
+            SkipIfValueNe { register, value } => 0x4000 | x(register) | *value as u16,
+            SkipIfRegistersEq { register_x, register_y } => 0x5000 | x(register_x) | y(register_y),
+            RegisterValueStore { register, value } => 0x6000 | x(register) | *value as u16,
+            RegisterValueAdd { register, value } => 0x7000 | x(register) | *value as u16,
+            RegistersCopy { register_to, register_from } => 0x8000 | x(register_to) | y(register_from),
+            RegistersOrEq { register_to, register_from } => 0x8001 | x(register_to) | y(register_from),
+            RegistersAndEq { register_to, register_from } => 0x8002 | x(register_to) | y(register_from),
+            RegistersXorEq { register_to, register_from } => 0x8003 | x(register_to) | y(register_from),
+            RegistersAdd { register_to, register_from } => 0x8004 | x(register_to) | y(register_from),
+            RegistersSub { register_to, register_from } => 0x8005 | x(register_to) | y(register_from),
+            RegistersShiftRightEq { register_to, register_from } => 0x8006 | x(register_to) | y(register_from),
+            RegistersSubReversed { register_to, register_from } => 0x8007 | x(register_to) | y(register_from),
+            RegistersShiftLeftEq { register_to, register_from } => 0x800E | x(register_to) | y(register_from),
+            SkipIfRegistersNe { register_x, register_y } => 0x9000 | x(register_x) | y(register_y),
+            IStoreAddress { address } => 0xA000 | address,
+            GotoOffsetted { address } => 0xB000 | address,
+            RegisterStoreRandom { register, mask } => 0xC000 | x(register) | *mask as u16,
+            DrawSprite { register_x, register_y, sprite_height } =>
+                0xD000 | x(register_x) | y(register_y) | *sprite_height as u16,
+            SkipIfKeyOn { register } => 0xE09E | x(register),
+            SkipIfKeyOff { register } => 0xE0A1 | x(register),
+            LoadAudioPattern => 0xF002,
+            DelayTimerToRegister { register } => 0xF007 | x(register),
+            WaitForAnyKey { register } => 0xF00A | x(register),
+            RegisterToDelayTimer { register } => 0xF015 | x(register),
+            RegisterToSoundTimer { register } => 0xF018 | x(register),
+            SetAudioPitch { register } => 0xF03A | x(register),
+            IAddOffset { register } => 0xF01E | x(register),
+            IStoreDigitAddress { register } => 0xF029 | x(register),
+            IStoreBigDigitAddress { register } => 0xF030 | x(register),
+            HexToDecimal { register } => 0xF033 | x(register),
+            RegistersDump { max_register } => 0xF055 | x(max_register),
+            RegistersLoad { max_register } => 0xF065 | x(max_register),
+            SaveFlags { max_register } => 0xF075 | x(max_register),
+            LoadFlags { max_register } => 0xF085 | x(max_register),
+        };
+        Some(opcode)
+    }
+}
+
+/// Renders the instruction as assembly, using the conventional mnemonics from Cowgod's technical reference.
+/// Bytes and addresses are written in hex, while sprite heights and scroll amounts are written in decimal.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unimplemented { opcode } => write!(f, "DW 0x{:04X}", opcode),
+            EndProgram => write!(f, "END"),
+            ClearScreen => write!(f, "CLS"),
+            ReturnSubroutine => write!(f, "RET"),
+            ScrollDown { amount } => write!(f, "SCD {}", amount),
+            ScrollUp { amount } => write!(f, "SCU {}", amount),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            DisableHighRes => write!(f, "LOW"),
+            EnableHighRes => write!(f, "HIGH"),
+            SelectPlanes { mask } => write!(f, "PLANE {}", mask),
+            SysCall { address } => write!(f, "SYS 0x{:03X}", address),
+            Goto { address } => write!(f, "JP 0x{:03X}", address),
+            CallSubroutine { address } => write!(f, "CALL 0x{:03X}", address),
+            SkipIfValueEq { register, value } => write!(f, "SE {}, 0x{:02X}", register, value),
+            SkipIfValueNe { register, value } => write!(f, "SNE {}, 0x{:02X}", register, value),
+            SkipIfRegistersEq { register_x, register_y } => write!(f, "SE {}, {}", register_x, register_y),
+            RegisterValueStore { register, value } => write!(f, "LD {}, 0x{:02X}", register, value),
+            RegisterValueAdd { register, value } => write!(f, "ADD {}, 0x{:02X}", register, value),
+            RegistersCopy { register_to, register_from } => write!(f, "LD {}, {}", register_to, register_from),
+            RegistersOrEq { register_to, register_from } => write!(f, "OR {}, {}", register_to, register_from),
+            RegistersAndEq { register_to, register_from } => write!(f, "AND {}, {}", register_to, register_from),
+            RegistersXorEq { register_to, register_from } => write!(f, "XOR {}, {}", register_to, register_from),
+            RegistersAdd { register_to, register_from } => write!(f, "ADD {}, {}", register_to, register_from),
+            RegistersSub { register_to, register_from } => write!(f, "SUB {}, {}", register_to, register_from),
+            RegistersShiftRightEq { register_to, register_from } => write!(f, "SHR {}, {}", register_to, register_from),
+            RegistersSubReversed { register_to, register_from } => write!(f, "SUBN {}, {}", register_to, register_from),
+            RegistersShiftLeftEq { register_to, register_from } => write!(f, "SHL {}, {}", register_to, register_from),
+            SkipIfRegistersNe { register_x, register_y } => write!(f, "SNE {}, {}", register_x, register_y),
+            IStoreAddress { address } => write!(f, "LD I, 0x{:03X}", address),
+            IStoreLongAddress { address } => write!(f, "LD I, LONG 0x{:04X}", address),
+            GotoOffsetted { address } => write!(f, "JP V0, 0x{:03X}", address),
+            RegisterStoreRandom { register, mask } => write!(f, "RND {}, 0x{:02X}", register, mask),
+            DrawSprite { register_x, register_y, sprite_height } =>
+                write!(f, "DRW {}, {}, {}", register_x, register_y, sprite_height),
+            SkipIfKeyOn { register } => write!(f, "SKP {}", register),
+            SkipIfKeyOff { register } => write!(f, "SKNP {}", register),
+            LoadAudioPattern => write!(f, "AUDIO"),
+            DelayTimerToRegister { register } => write!(f, "LD {}, DT", register),
+            WaitForAnyKey { register } => write!(f, "LD {}, K", register),
+            RegisterToDelayTimer { register } => write!(f, "LD DT, {}", register),
+            RegisterToSoundTimer { register } => write!(f, "LD ST, {}", register),
+            SetAudioPitch { register } => write!(f, "PITCH {}", register),
+            IAddOffset { register } => write!(f, "ADD I, {}", register),
+            IStoreDigitAddress { register } => write!(f, "LD F, {}", register),
+            IStoreBigDigitAddress { register } => write!(f, "LD HF, {}", register),
+            HexToDecimal { register } => write!(f, "LD B, {}", register),
+            RegistersDump { max_register } => write!(f, "LD [I], {}", max_register),
+            RegistersLoad { max_register } => write!(f, "LD {}, [I]", max_register),
+            SaveFlags { max_register } => write!(f, "LD R, {}", max_register),
+            LoadFlags { max_register } => write!(f, "LD {}, R", max_register),
         }
     }
 }
@@ -215,6 +478,31 @@ mod tests {
 
         let instr = get_instr(0x00EE);
         assert_eq!(instr, ReturnSubroutine);
+
+        let instr = get_instr(0x00C5);
+        assert_eq!(instr, ScrollDown {amount: 5});
+
+        let instr = get_instr(0x00D5);
+        assert_eq!(instr, ScrollUp {amount: 5});
+
+        let instr = get_instr(0x00DE);
+        assert_eq!(instr, EndProgram);
+
+        let instr = get_instr(0x00FB);
+        assert_eq!(instr, ScrollRight);
+
+        let instr = get_instr(0x00FC);
+        assert_eq!(instr, ScrollLeft);
+
+        let instr = get_instr(0x00FE);
+        assert_eq!(instr, DisableHighRes);
+
+        let instr = get_instr(0x00FF);
+        assert_eq!(instr, EnableHighRes);
+
+        let instr = get_instr(0x0123);
+        assert_eq!(instr, SysCall {address: 0x123});
+        assert_eq!(instr.to_string(), "SYS 0x123");
     }
 
     #[test]
@@ -254,6 +542,9 @@ mod tests {
             register_x: Register::new(0xA),
             register_y: Register::new(0xB),
         });
+
+        let instr = get_instr(0x5AB1);
+        assert_eq!(instr, Unimplemented {opcode: 0x5AB1});
     }
 
     #[test]
@@ -338,6 +629,9 @@ mod tests {
             register_x: Register::first(),
             register_y: Register::new(0xF),
         });
+
+        let instr = get_instr(0x90F1);
+        assert_eq!(instr, Unimplemented {opcode: 0x90F1});
     }
 
     #[test]
@@ -382,6 +676,18 @@ mod tests {
 
     #[test]
     pub fn test_c15() {
+        let instr = get_instr(0xF301);
+        assert_eq!(instr, SelectPlanes {mask: 3});
+
+        let instr = get_instr(0xF002);
+        assert_eq!(instr, LoadAudioPattern);
+
+        let instr = get_instr(0xF102);
+        assert_eq!(instr, Unimplemented {opcode: 0xF102});
+
+        let instr = get_instr(0xFA3A);
+        assert_eq!(instr, SetAudioPitch {register: Register::new(0xA)});
+
         let instr = get_instr(0xF007);
         assert_eq!(instr, DelayTimerToRegister {register: Register::first()});
 
@@ -400,6 +706,9 @@ mod tests {
         let instr = get_instr(0xF029);
         assert_eq!(instr, IStoreDigitAddress {register: Register::first()});
 
+        let instr = get_instr(0xF130);
+        assert_eq!(instr, IStoreBigDigitAddress {register: Register::new(0x1)});
+
         let instr = get_instr(0xF033);
         assert_eq!(instr, HexToDecimal {register: Register::first()});
 
@@ -408,5 +717,73 @@ mod tests {
 
         let instr = get_instr(0xF065);
         assert_eq!(instr, RegistersLoad {max_register: Register::first()});
+
+        let instr = get_instr(0xF775);
+        assert_eq!(instr, SaveFlags {max_register: Register::new(0x7)});
+
+        let instr = get_instr(0xF385);
+        assert_eq!(instr, LoadFlags {max_register: Register::new(0x3)});
+
+        let instr = Instruction::new(&[0xF0, 0x00, 0x0A, 0xBC], 0);
+        assert_eq!(instr, IStoreLongAddress {address: 0x0ABC});
+        assert_eq!(instr.size(), 4);
+        assert_eq!(instr.to_string(), "LD I, LONG 0x0ABC");
+
+        let instr = get_instr(0xF000);
+        assert_eq!(instr, Unimplemented {opcode: 0xF000}, "The address word should be required");
+    }
+
+    #[test]
+    pub fn test_try_decode() {
+        let mem = [0x00, 0xE0, 0x12, 0x00, 0x60];
+        assert_eq!(Instruction::try_decode(&mem, 2), Some(Goto {address: 0x200}));
+        assert_eq!(Instruction::try_decode(&mem, 3), Some(SysCall {address: 0x060}));
+        assert_eq!(Instruction::try_decode(&mem, 4), None);
+        assert_eq!(Instruction::try_decode(&mem, usize::MAX), None);
+        assert!(Word::try_new(&mem, 3).is_some());
+        assert!(Word::try_new(&mem, 4).is_none());
+    }
+
+    #[test]
+    pub fn test_display() {
+        assert_eq!(get_instr(0x00E0).to_string(), "CLS");
+        assert_eq!(get_instr(0x00C3).to_string(), "SCD 3");
+        assert_eq!(get_instr(0x00D3).to_string(), "SCU 3");
+        assert_eq!(get_instr(0xF201).to_string(), "PLANE 2");
+        assert_eq!(get_instr(0xF002).to_string(), "AUDIO");
+        assert_eq!(get_instr(0xF43A).to_string(), "PITCH V4");
+        assert_eq!(get_instr(0x12A0).to_string(), "JP 0x2A0");
+        assert_eq!(get_instr(0x3311).to_string(), "SE V3, 0x11");
+        assert_eq!(get_instr(0x8AB4).to_string(), "ADD VA, VB");
+        assert_eq!(get_instr(0xA2A0).to_string(), "LD I, 0x2A0");
+        assert_eq!(get_instr(0xB123).to_string(), "JP V0, 0x123");
+        assert_eq!(get_instr(0xD123).to_string(), "DRW V1, V2, 3");
+        assert_eq!(get_instr(0xF00A).to_string(), "LD V0, K");
+        assert_eq!(get_instr(0xF555).to_string(), "LD [I], V5");
+        assert_eq!(get_instr(0xF565).to_string(), "LD V5, [I]");
+        assert_eq!(get_instr(0x8AB8).to_string(), "DW 0x8AB8");
+    }
+
+    #[test]
+    pub fn test_encode_round_trip() {
+        let roms: [&[u8]; 3] = [
+            include_bytes!("../tests/roms/PONG.ch8"),
+            include_bytes!("../tests/roms/BRIX.ch8"),
+            include_bytes!("../tests/roms/INVADERS.ch8"),
+        ];
+        for rom in roms {
+            for pc in (0..rom.len() - 1).step_by(2) {
+                let opcode = u16::from_be_bytes([rom[pc], rom[pc + 1]]);
+                match Instruction::new(rom, pc) {
+                    instr @ (Unimplemented { .. } | EndProgram) => assert_eq!(instr.encode(), None),
+                    instr => assert_eq!(instr.encode(), Some(opcode), "{} at {:#05x}", instr, pc),
+                }
+            }
+        }
+
+        assert_eq!(get_instr(0x00FB).encode(), Some(0x00FB));
+        assert_eq!(get_instr(0xDAB5).encode(), Some(0xDAB5));
+        assert_eq!(get_instr(0x5AB1).encode(), None);
+        assert_eq!(get_instr(0x0000).encode(), None);
     }
 }
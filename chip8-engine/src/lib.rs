@@ -1,6 +1,44 @@
+//! A CHIP-8, SUPER-CHIP and XO-CHIP emulator core.
+//!
+//! The engine supports `#![no_std]` targets which have an allocator. The features are:
+//! - `std` (default): `Machine::from_reader`, `Machine::audio_playback_rate`, and random seeds for machines
+//!   created without one
+//! - `log` (default): logs executed and unimplemented instructions through the `log` crate
+//! - `serde`: serialization for `Quirks` and `MachineSnapshot`
+//! - `wasm`: a `wasm-bindgen` wrapper for driving a machine from JavaScript, see `examples/wasm`
+//!
+//! Building with `default-features = false` gives a `no_std` engine without any logging.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 extern crate core;
 
+/// Forwards to the `log` crate when the `log` feature is enabled, and compiles to nothing otherwise.
+#[cfg(feature = "log")]
+macro_rules! trace {
+    ($($arg:tt)*) => { log::trace!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! trace {
+    ($($arg:tt)*) => { { let _ = format_args!($($arg)*); } };
+}
+
+#[cfg(feature = "log")]
+macro_rules! warn {
+    ($($arg:tt)*) => { log::warn!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! warn {
+    ($($arg:tt)*) => { { let _ = format_args!($($arg)*); } };
+}
+
+mod asm;
+mod config;
 mod display;
+mod error;
 mod machine;
 mod stack;
 mod heap;
@@ -9,6 +47,21 @@ mod timers;
 mod instruction;
 mod word;
 mod quirks;
+mod replay;
+mod report;
+mod snapshot;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-pub use crate::machine::Machine;
-pub use crate::quirks::Quirks;
+pub use crate::asm::{assemble, AsmError};
+pub use crate::config::{MachineConfig, ZeroOpcode};
+pub use crate::error::MachineError;
+pub use crate::instruction::Instruction;
+pub use crate::machine::{Anomaly, DrawEvent, ExecutedInstruction, Machine, StopReason, TimerKind, TraceCallback, FLAG_COUNT};
+pub use crate::quirks::{DumpIndex, Quirks, QuirksBuilder};
+pub use crate::registers::Register;
+pub use crate::replay::{InputLog, InputLogError};
+pub use crate::report::RomReport;
+pub use crate::snapshot::MachineSnapshot;
+#[cfg(feature = "wasm")]
+pub use crate::wasm::WasmMachine;
@@ -1,12 +1,122 @@
-use log::*;
-use crate::display::Display;
-use crate::Quirks;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+use crate::display::{self, Display};
+use crate::MachineError;
+use crate::{DumpIndex, MachineConfig, MachineSnapshot, Quirks, ZeroOpcode};
 use crate::{heap, heap::Heap};
 use crate::instruction::Instruction;
 use crate::registers::{Register, Registers};
+use crate::report::RomReport;
 use crate::stack::Stack;
 use crate::timers::Timers;
 
+const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_AUDIO_PITCH: u8 = 64;
+/// SUPER-CHIP has room for `V0` to `V7` in its RPL user flags.
+pub const FLAG_COUNT: usize = 8;
+
+/// Machines created without a seed use this one when there's no `std` to provide entropy.
+#[cfg(not(feature = "std"))]
+const DEFAULT_SEED: u64 = 0x8A5C_D789_635D_2DFF;
+
+#[cfg(feature = "std")]
+fn new_rng() -> fastrand::Rng {
+    fastrand::Rng::new()
+}
+
+#[cfg(not(feature = "std"))]
+fn new_rng() -> fastrand::Rng {
+    fastrand::Rng::with_seed(DEFAULT_SEED)
+}
+
+/// Packs the pressed keys into a bitmask, skipping any which aren't one of the 16 hex keys.
+fn key_mask(keys_pressed: &[u8]) -> u16 {
+    keys_pressed
+        .iter()
+        .filter(|&&key| key <= 0xF)
+        .fold(0, |mask, &key| mask | (1 << key))
+}
+
+fn is_key_down(keys: u16, key: u8) -> bool {
+    key <= 0xF && keys & (1 << key) != 0
+}
+
+/// Describes an instruction which was run by `Machine::step`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ExecutedInstruction {
+    pub instruction: Instruction,
+    pub pc_before: usize,
+    pub pc_after: usize,
+}
+
+/// A callback given to `Machine::set_trace_callback`, which is called with the machine, program counter and
+/// instruction before each instruction is executed.
+pub type TraceCallback = Box<dyn FnMut(&Machine, usize, Instruction)>;
+
+/// Describes a sprite drawn by `DrawSprite`, as reported to the callback given to `Machine::set_draw_callback`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawEvent {
+    /// The position the sprite was drawn at, taken from the X and Y registers
+    pub x: usize,
+    pub y: usize,
+    /// The number of rows in the sprite, which is 16 for a large SUPER-CHIP sprite
+    pub height: usize,
+    /// Whether any lit pixels were erased, which is what VF is set to
+    pub is_collision: bool,
+}
+
+/// One of the two timers, as reported to the callback given to `Machine::set_timer_callback`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TimerKind {
+    Delay,
+    Sound,
+}
+
+/// Something a program did which is legal, but is often a bug, as recorded while anomaly tracking is enabled
+/// with `Machine::set_anomaly_tracking`. Each holds the address of the instruction responsible.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Anomaly {
+    /// Adding to the register with `7XNN` or `8XY4` wrapped around past 0xFF
+    RegisterOverflow { pc: usize, register: Register },
+
+    /// Adding to `I` with `FX1E` went past the end of memory, so it wrapped around to the start
+    IndexOverflow { pc: usize },
+
+    /// A subroutine was called while the stack was full, which stops the program with an error
+    StackOverflow { pc: usize },
+
+    /// A subroutine returned while the stack was empty, which stops the program with an error
+    StackUnderflow { pc: usize },
+}
+
+/// Describes why `Machine::run_until_breakpoint` stopped executing.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StopReason {
+    /// The program counter reached an address with a breakpoint
+    Breakpoint { address: usize },
+
+    /// The program has ended, or is stuck jumping to itself
+    EndOfProgram,
+
+    /// The program is waiting for a key to be pressed
+    WaitingForKey,
+}
+
+/// What should happen after an instruction has been executed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Flow {
+    /// Carry on with the next instruction
+    Continue,
+    /// The program counter was left where it was, e.g. while waiting for a key or once the program has ended
+    Pause,
+    /// No more instructions should run until the next 60Hz frame
+    EndFrame,
+}
+
 pub struct Machine {
     heap: Heap,
     stack: Stack,
@@ -14,38 +124,716 @@ pub struct Machine {
     timers: Timers,
     display: Display,
     quirks: Quirks,
+    config: MachineConfig,
+    rng: fastrand::Rng,
+    /// The key which was pressed during a `WaitForAnyKey`, which completes once it's released
+    pressed_key: Option<u8>,
+    /// Keys which were held down when a `WaitForAnyKey` completed, as a bit for each key. These count as released
+    /// until they really are, so a key held through a menu's wait doesn't also trigger the next key check.
+    consumed_keys: u16,
+    /// Keys held down as of the last `set_keys`, which every instruction sees along with the keys passed to it
+    latched_keys: u16,
+    /// The XO-CHIP audio pattern, which replaces the default buzzer tone once loaded
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    audio_pitch: u8,
+    /// The SUPER-CHIP RPL user flags, which programs use as storage that outlives a single run
+    flags: [u8; FLAG_COUNT],
+    breakpoints: BTreeSet<usize>,
+    trace_callback: Option<TraceCallback>,
+    draw_callback: Option<Box<dyn FnMut(DrawEvent)>>,
+    timer_callback: Option<Box<dyn FnMut(TimerKind)>>,
+    cycle_count: u64,
+    is_profiling: bool,
+    profile: BTreeMap<&'static str, u64>,
+    is_tracking_anomalies: bool,
+    anomalies: Vec<Anomaly>,
+    /// Snapshots taken before each `step`, newest last, holding up to `history_depth` of them
+    history: VecDeque<MachineSnapshot>,
+    history_depth: usize,
 }
 
 impl Machine {
-    pub fn new(program_bytes: Vec<u8>, quirks: Quirks) -> Self {
-        Machine {
-            heap: Heap::new(program_bytes),
-            stack: Stack::new(),
-            registers: Registers::new(),
+    /// Panics if the program doesn't fit in memory, see `try_new` for a checked alternative.
+    pub fn new(program_bytes: impl AsRef<[u8]>, quirks: Quirks) -> Self {
+        Machine::with_config(program_bytes, quirks, MachineConfig::default())
+    }
+
+    /// Like `new`, but returns `MachineError::ProgramTooLarge` if the program doesn't fit in memory.
+    pub fn try_new(program_bytes: impl AsRef<[u8]>, quirks: Quirks) -> Result<Self, MachineError> {
+        Machine::try_with_config(program_bytes, quirks, MachineConfig::default())
+    }
+
+    /// Creates a machine with only the fonts in memory, for programs to be written in with `write_memory`.
+    pub fn new_empty(quirks: Quirks) -> Self {
+        Machine::with_rng(&[], quirks, MachineConfig::default(), new_rng()).expect("An empty program should always fit in memory")
+    }
+
+    /// Reads a program from `reader` and loads it, without needing the whole source up front.
+    /// Programs which don't fit in the memory after `0x200` are rejected with `InvalidData`.
+    #[cfg(feature = "std")]
+    pub fn from_reader(reader: impl Read, quirks: Quirks) -> io::Result<Self> {
+        let capacity = MachineConfig::default().memory_size - heap::OFFSET_DATA;
+        let mut program_bytes = Vec::new();
+        // Reading a byte past the capacity is enough to tell whether the program is too large.
+        reader.take(capacity as u64 + 1).read_to_end(&mut program_bytes)?;
+        if program_bytes.len() > capacity {
+            let message = format!("Program is larger than the {} bytes of memory available to it", capacity);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        Ok(Machine::new(program_bytes, quirks))
+    }
+
+    pub fn with_config(program_bytes: impl AsRef<[u8]>, quirks: Quirks, config: MachineConfig) -> Self {
+        Machine::try_with_config(program_bytes, quirks, config).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_with_config(program_bytes: impl AsRef<[u8]>, quirks: Quirks, config: MachineConfig) -> Result<Self, MachineError> {
+        Machine::with_rng(program_bytes.as_ref(), quirks, config, new_rng())
+    }
+
+    /// Creates a machine whose random numbers are generated deterministically from `seed`.
+    /// Two machines with the same seed, program, and input will always behave identically.
+    pub fn with_seed(program_bytes: impl AsRef<[u8]>, quirks: Quirks, seed: u64) -> Self {
+        Machine::with_rng(program_bytes.as_ref(), quirks, MachineConfig::default(), fastrand::Rng::with_seed(seed))
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Restarts the random number generator from `seed`, e.g. to replay a session recorded in an `InputLog`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.seed(seed);
+    }
+
+    pub(crate) fn with_rng(program_bytes: &[u8], quirks: Quirks, config: MachineConfig, rng: fastrand::Rng) -> Result<Self, MachineError> {
+        Ok(Machine {
+            heap: Heap::new(program_bytes, config.memory_size, config.load_address)?,
+            stack: Stack::with_capacity(config.stack_capacity),
+            registers: Registers::new(config.load_address),
             timers: Timers::new(),
-            display: Display::new(),
-            quirks
+            display: Display::with_resolution(config.resolution.0, config.resolution.1),
+            quirks,
+            config,
+            rng,
+            pressed_key: None,
+            consumed_keys: 0,
+            audio_pattern: None,
+            audio_pitch: DEFAULT_AUDIO_PITCH,
+            flags: [0; FLAG_COUNT],
+            latched_keys: 0,
+            breakpoints: BTreeSet::new(),
+            trace_callback: None,
+            draw_callback: None,
+            timer_callback: None,
+            cycle_count: 0,
+            is_profiling: false,
+            profile: BTreeMap::new(),
+            is_tracking_anomalies: false,
+            anomalies: Vec::new(),
+            history: VecDeque::new(),
+            history_depth: 0,
+        })
+    }
+
+    /// Restarts the loaded program from the beginning.
+    /// Memory and the RPL user flags are left as-is, so the program bytes and fonts don't need to be reloaded.
+    pub fn reset(&mut self) {
+        self.stack = Stack::with_capacity(self.config.stack_capacity);
+        self.registers = Registers::new(self.config.load_address);
+        self.timers = Timers::new();
+        self.pressed_key = None;
+        self.consumed_keys = 0;
+        self.audio_pattern = None;
+        self.audio_pitch = DEFAULT_AUDIO_PITCH;
+        self.display.set_high_res(false);
+        self.display.set_plane_mask(1);
+        self.display.clear_all();
+        self.cycle_count = 0;
+        self.profile.clear();
+        self.anomalies.clear();
+        self.history.clear();
+    }
+
+    /// The active display resolution as `(width, height)`.
+    /// `draw` expects a frame of `width * height * 4` bytes.
+    pub fn resolution(&self) -> (usize, usize) {
+        (self.display.width(), self.display.height())
+    }
+
+    pub fn snapshot(&self) -> MachineSnapshot {
+        MachineSnapshot {
+            memory: self.heap.get_all_bytes().to_vec(),
+            registers: self.registers.snapshot().to_vec(),
+            index: self.registers.index,
+            program_counter: self.registers.program_counter,
+            stack: self.stack.frames().to_vec(),
+            delay_timer: self.timers.delay,
+            sound_timer: self.timers.sound,
+            pressed_key: self.pressed_key,
+            consumed_keys: self.consumed_keys,
+            audio_pattern: self.audio_pattern,
+            audio_pitch: self.audio_pitch,
+            flags: self.flags,
+            is_high_res: self.display.is_high_res(),
+            pixels: self.display.pixels().to_vec(),
+            plane_mask: self.display.plane_mask(),
+            quirks: self.quirks,
+            rng_seed: self.rng.get_seed(),
+        }
+    }
+
+    /// Returns the machine to the state captured by `snapshot`.
+    /// Fails without changing anything if the snapshot holds more stack frames or memory than the machine supports,
+    /// or pixels which don't match its resolution.
+    pub fn restore(&mut self, snapshot: &MachineSnapshot) -> Result<(), MachineError> {
+        if snapshot.stack.len() > self.config.stack_capacity {
+            return Err(MachineError::StackOverflow);
         }
+        self.heap.check_bounds(0, snapshot.memory.len())?;
+        self.display.load_pixels(snapshot.is_high_res, &snapshot.pixels)?;
+
+        self.stack = Stack::with_capacity(self.config.stack_capacity);
+        for &frame in &snapshot.stack {
+            self.stack.push(frame)?;
+        }
+
+        self.heap.set_bytes(0, &snapshot.memory)?;
+        self.registers.load(&snapshot.registers);
+        self.registers.index = snapshot.index;
+        self.registers.program_counter = snapshot.program_counter;
+        self.timers.delay = snapshot.delay_timer;
+        self.timers.sound = snapshot.sound_timer;
+        self.pressed_key = snapshot.pressed_key;
+        self.consumed_keys = snapshot.consumed_keys;
+        self.audio_pattern = snapshot.audio_pattern;
+        self.audio_pitch = snapshot.audio_pitch;
+        self.flags = snapshot.flags;
+        self.display.set_plane_mask(snapshot.plane_mask);
+        self.quirks = snapshot.quirks;
+        self.rng.seed(snapshot.rng_seed);
+        Ok(())
+    }
+
+    /// The current pixels in row-major order, sized for the active resolution.
+    /// Each pixel has a bit set for every XO-CHIP plane it's lit on, so plain CHIP-8 programs only produce 0 or 1.
+    /// This allows the machine to be run and inspected without any window or RGBA buffer.
+    pub fn framebuffer(&self) -> &[u8] {
+        self.display.pixels()
+    }
+
+    /// The `(x, y)` positions of the pixels which have changed since `previous` was copied from `framebuffer`,
+    /// so front-ends can redraw only what's changed.
+    pub fn dirty_since(&self, previous: &[u8]) -> Vec<(usize, usize)> {
+        self.display.dirty_since(previous)
+    }
+
+    /// The screen bit-packed into a few hundred bytes, for streaming to a thin client which draws it with
+    /// `load_serialized_display`. A header byte holding the resolution mode is followed by 8 pixels per byte
+    /// for the first plane, and for the second XO-CHIP plane if any pixel is lit on it.
+    pub fn serialize_display(&self) -> Vec<u8> {
+        self.display.serialize()
+    }
+
+    /// Replaces the screen with one sent by `serialize_display`, which must come from a machine with the same
+    /// resolution. Only the pixels and resolution mode change, so the machine can be a `new_empty` one used for drawing.
+    pub fn load_serialized_display(&mut self, data: &[u8]) -> Result<(), MachineError> {
+        Ok(self.display.deserialize(data)?)
+    }
+
+    /// Sets the RGB colors used for lit (`foreground`) and unlit (`background`) pixels on the first plane.
+    pub fn set_colors(&mut self, foreground: (u8, u8, u8), background: (u8, u8, u8)) {
+        self.display.set_colors(foreground, background);
+    }
+
+    /// Sets the RGB colors for each pixel value in `framebuffer`, which covers all four XO-CHIP plane combinations.
+    pub fn set_palette(&mut self, palette: [(u8, u8, u8); 4]) {
+        self.display.set_palette(palette);
+    }
+
+    pub fn is_inverted(&self) -> bool {
+        self.display.is_inverted()
+    }
+
+    /// Swaps the colors of lit and unlit pixels in the rendered output.
+    pub fn set_inverted(&mut self, is_inverted: bool) {
+        self.display.set_inverted(is_inverted);
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.display.brightness()
+    }
+
+    /// Dims the rendered output, from 0.0 (black) to 1.0 (full brightness).
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.display.set_brightness(brightness);
+    }
+
+    /// Keeps pixels which turn off partially lit for `frames` frames, which hides the flicker of sprites
+    /// being erased and redrawn. Fading is counted down by `tick_timers` and `tick_many`.
+    pub fn set_persistence(&mut self, frames: u8) {
+        self.display.set_persistence(frames);
+    }
+
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.display.to_rgba()
+    }
+
+    pub fn to_ascii(&self) -> String {
+        self.display.to_ascii()
     }
 
     pub fn draw(&self, frame: &mut [u8]) {
         self.display.draw(frame);
     }
 
+    /// Draws into `frame` like `draw`, but only if the screen has changed since the last call to this,
+    /// returning whether it did. Front-ends can skip presenting a frame which wasn't drawn.
+    pub fn draw_if_dirty(&mut self, frame: &mut [u8]) -> bool {
+        self.display.draw_if_dirty(frame)
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.timers.is_beeping()
+    }
+
+    /// The 128 one-bit samples loaded by an XO-CHIP program, or `None` to use the default buzzer tone.
+    pub fn audio_pattern(&self) -> Option<[u8; AUDIO_PATTERN_SIZE]> {
+        self.audio_pattern
+    }
+
+    pub fn audio_pitch(&self) -> u8 {
+        self.audio_pitch
+    }
+
+    /// The rate in samples per second at which the audio pattern plays, derived from the pitch.
+    /// The default pitch of 64 plays at 4000Hz, and every 48 steps doubles or halves the rate.
+    /// This needs `std` for floating point math.
+    #[cfg(feature = "std")]
+    pub fn audio_playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.audio_pitch as f32 - DEFAULT_AUDIO_PITCH as f32) / 48.0)
+    }
+
+    /// Writes `bytes` into memory starting at `address`, which is handy for setting up tests.
+    /// Nothing is written if any of the bytes would land past the end of memory.
+    pub fn write_memory(&mut self, address: usize, bytes: &[u8]) -> Result<(), MachineError> {
+        self.heap.set_bytes(address, bytes)?;
+        Ok(())
+    }
+
+    /// Replaces the built-in font used by `IStoreDigitAddress`, as 5 bytes for each hex digit from 0 to F.
+    /// The font is kept in memory, so it survives a `reset`.
+    pub fn set_font(&mut self, font: &[u8]) -> Result<(), MachineError> {
+        self.heap.set_font(font)?;
+        Ok(())
+    }
+
+    /// Reads the `len` bytes of memory starting at `address`.
+    pub fn read_memory(&self, address: usize, len: usize) -> Result<&[u8], MachineError> {
+        self.heap.check_bounds(address, len)?;
+        Ok(self.heap.get_bytes(address, len))
+    }
+
+    /// The `len` bytes of memory starting at `address`, for front-ends to display as a hex dump.
+    pub fn memory_dump(&self, address: usize, len: usize) -> Result<&[u8], MachineError> {
+        self.heap.check_bounds(address, len)?;
+        Ok(self.heap.get_bytes(address, len))
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.registers.program_counter
+    }
+
+    /// Moves execution to `address`, e.g. to start a debugging session partway through a program.
+    /// Fails if the address is past the end of memory.
+    pub fn set_program_counter(&mut self, address: usize) -> Result<(), MachineError> {
+        self.heap.check_bounds(address, 1)?;
+        self.registers.program_counter = address;
+        Ok(())
+    }
+
+    pub fn index(&self) -> usize {
+        self.registers.index
+    }
+
+    /// Points the `I` register at `address`. Fails if the address is past the end of memory.
+    pub fn set_index(&mut self, address: usize) -> Result<(), MachineError> {
+        self.heap.check_bounds(address, 1)?;
+        self.registers.index = address;
+        Ok(())
+    }
+
+    pub fn register(&self, register: Register) -> u8 {
+        self.registers.get_value(register)
+    }
+
+    /// Sets a general register, for setting up a machine's state without a program which loads the values.
+    pub fn set_register(&mut self, register: Register, value: u8) {
+        self.registers.set_value(register, value);
+    }
+
+    /// A copy of the general registers from V0 to VF.
+    pub fn registers(&self) -> [u8; 16] {
+        self.registers.snapshot()
+    }
+
+    /// The SUPER-CHIP RPL user flags, which frontends can save to keep them between runs.
+    pub fn flags(&self) -> [u8; FLAG_COUNT] {
+        self.flags
+    }
+
+    /// Replaces the RPL user flags, e.g. with ones saved from a previous run.
+    pub fn set_flags(&mut self, flags: [u8; FLAG_COUNT]) {
+        self.flags = flags;
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack.depth()
+    }
+
+    /// The return addresses of the subroutines currently being run, from the outermost call to the innermost.
+    pub fn call_stack(&self) -> &[usize] {
+        self.stack.frames()
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.timers.delay
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.timers.sound
+    }
+
+    /// Decodes the instruction at the program counter without executing it, for showing what `step` will run next.
+    /// Fails in the same case as `step`, when the program counter is on a zero opcode that's configured to fail.
+    pub fn peek_instruction(&self) -> Result<Instruction, MachineError> {
+        self.decode()
+    }
+
+    /// Executes exactly one instruction and reports what ran, for use by debuggers.
+    /// Like `tick`, this doesn't touch the timers.
+    pub fn step(&mut self, keys_pressed: &[u8]) -> Result<ExecutedInstruction, MachineError> {
+        if self.history_depth > 0 {
+            if self.history.len() == self.history_depth {
+                self.history.pop_front();
+            }
+            self.history.push_back(self.snapshot());
+        }
+        let pc_before = self.registers.program_counter;
+        let instruction = self.decode()?;
+        self.execute(instruction, key_mask(keys_pressed))?;
+        Ok(ExecutedInstruction {
+            instruction,
+            pc_before,
+            pc_after: self.registers.program_counter,
+        })
+    }
+
+    /// Latches the keys held down, which every instruction sees until the next call, on top of any keys passed
+    /// to `step` or a tick. Front-ends should call this once per frame with the keys polled for that frame, and pass
+    /// no keys to the ticks themselves. Input is then fixed at frame boundaries, so every instruction in a frame
+    /// agrees on which keys are held, just as a program on the original hardware could expect within a frame.
+    pub fn set_keys(&mut self, keys_pressed: &[u8]) {
+        self.latched_keys = key_mask(keys_pressed);
+    }
+
+    /// Executes a single instruction, without touching the timers.
+    /// Callers driving the machine one instruction at a time should call `tick_timers` once per 60Hz frame.
+    ///
+    /// This never panics: a program which misbehaves, e.g. by returning with an empty stack or
+    /// reading past the end of memory, halts with an error and leaves the program counter on the
+    /// offending instruction.
+    pub fn try_tick(&mut self, keys_pressed: &[u8]) -> Result<(), MachineError> {
+        self.tick_with_keys(key_mask(keys_pressed))
+    }
+
+    /// Panics if the program misbehaves, see `try_tick` for a checked alternative.
     pub fn tick(&mut self, keys_pressed: Vec<u8>) {
+        self.try_tick(&keys_pressed).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// The same as `try_tick`, with the pressed keys as a bitmask where bit `n` is set while key `n` is held down.
+    pub fn tick_with_keys(&mut self, keys: u16) -> Result<(), MachineError> {
+        self.execute(self.decode()?, keys)?;
+        Ok(())
+    }
+
+    /// Counts the delay and sound timers down, as happens once per 60Hz frame. This also fades out unlit pixels.
+    pub fn tick_timers(&mut self) {
+        let (delay, sound) = (self.timers.delay, self.timers.sound);
+        self.timers.tick_60hz();
+        if let Some(callback) = &mut self.timer_callback {
+            if delay > 0 && self.timers.delay == 0 {
+                callback(TimerKind::Delay);
+            }
+            if sound > 0 && self.timers.sound == 0 {
+                callback(TimerKind::Sound);
+            }
+        }
+        self.display.end_frame();
+    }
+
+    /// Executes up to `cycles` instructions as a single 60Hz frame, so the timers are only ticked once.
+    /// Every instruction in the frame sees the same keys, `keys_pressed` along with any latched by `set_keys`.
+    /// The frame ends early if the program pauses, e.g. while waiting for a key or once it has ended,
+    /// or after drawing a sprite when the display wait quirk is active.
+    ///
+    /// Returns the number of instructions which ran, including the one which paused the program.
+    pub fn tick_many(&mut self, keys_pressed: &[u8], cycles: usize) -> Result<usize, MachineError> {
+        let keys = key_mask(keys_pressed);
+        let mut executed = 0;
+        while executed < cycles {
+            executed += 1;
+            if self.execute(self.decode()?, keys)? != Flow::Continue {
+                break;
+            }
+        }
+        self.tick_timers();
+        Ok(executed)
+    }
+
+    /// The keys which the next instruction checks, in ascending order, e.g. to highlight them on an on-screen keypad.
+    /// Waiting for a keypress is interested in every key, while instructions which don't read the keys have none.
+    pub fn keys_of_interest(&self) -> Vec<u8> {
+        match self.decode() {
+            Ok(Instruction::SkipIfKeyOn { register } | Instruction::SkipIfKeyOff { register }) => {
+                let key = self.registers.get_value(register);
+                (key <= 0xF).then_some(key).into_iter().collect()
+            }
+            Ok(Instruction::WaitForAnyKey { .. }) => (0..=0xF).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether the program has finished, because it reached the end of the program or a jump to itself.
+    /// Ticking a halted machine only counts down the timers.
+    pub fn is_halted(&self) -> bool {
+        let pc = self.registers.program_counter;
+        match self.decode() {
+            Ok(Instruction::EndProgram) => true,
+            Ok(Instruction::Goto { address }) => address as usize == pc,
+            Ok(Instruction::GotoOffsetted { address }) => self.offset_jump_address(address) == pc,
+            _ => false,
+        }
+    }
+
+    /// Scans the loaded program for signs that it isn't CHIP-8 code, such as a high share of unknown opcodes.
+    pub fn analyze(&self) -> RomReport {
+        RomReport::new(self.heap.get_all_bytes(), self.config.load_address)
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) {
+        self.breakpoints.remove(&address);
+    }
+
+    /// Executes instructions until the program counter lands on a breakpoint, the program ends,
+    /// or it has to wait for a key. At least one instruction is always executed, so this can be
+    /// called again to continue from a breakpoint. The timers are not ticked while running.
+    pub fn run_until_breakpoint(&mut self, keys_pressed: &[u8]) -> Result<StopReason, MachineError> {
+        let keys = key_mask(keys_pressed);
+        loop {
+            let instruction = self.decode()?;
+            if self.execute(instruction, keys)? == Flow::Pause {
+                return Ok(match instruction {
+                    Instruction::WaitForAnyKey { .. } => StopReason::WaitingForKey,
+                    _ => StopReason::EndOfProgram,
+                });
+            }
+
+            let pc = self.registers.program_counter;
+            if self.breakpoints.contains(&pc) {
+                return Ok(StopReason::Breakpoint { address: pc });
+            }
+        }
+    }
+
+    /// Decodes the address range `start..end` into instructions and their assembly text, one after another.
+    /// Data stored within the range is decoded as well, and usually shows up as `Unimplemented`.
+    pub fn disassemble_range(&self, start: usize, end: usize) -> Vec<(usize, Instruction, String)> {
+        let memory = self.heap.get_all_bytes();
+        let memory = &memory[..end.min(memory.len())];
+        let mut listing = Vec::new();
+        let mut address = start;
+        while address + 1 < memory.len() {
+            let instruction = Instruction::new(memory, address);
+            listing.push((address, instruction, instruction.to_string()));
+            address += instruction.size();
+        }
+        listing
+    }
+
+    /// Registers a callback which is given the machine, program counter and instruction right before each
+    /// instruction is executed, so the registers can be inspected along the way. Instructions are also logged
+    /// at the trace level.
+    pub fn set_trace_callback(&mut self, callback: TraceCallback) {
+        self.trace_callback = Some(callback);
+    }
+
+    /// Registers a callback which is told about each sprite right after it's drawn,
+    /// e.g. to trigger haptic feedback on collisions.
+    pub fn set_draw_callback(&mut self, callback: Box<dyn FnMut(DrawEvent)>) {
+        self.draw_callback = Some(callback);
+    }
+
+    /// Registers a callback which is told when a timer counts down to zero, e.g. to end a sound effect
+    /// exactly when the beep stops. Timers which are set to zero by the program don't count.
+    pub fn set_timer_callback(&mut self, callback: Box<dyn FnMut(TimerKind)>) {
+        self.timer_callback = Some(callback);
+    }
+
+    /// The number of instructions executed since the machine was created or reset.
+    pub fn cycles(&self) -> u64 {
+        self.cycle_count
+    }
+
+    /// Keeps the state from before each of the last `depth` calls to `step`, so they can be undone with `step_back`.
+    /// History is disabled with the default depth of 0, since taking a snapshot every step slows down execution.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.history_depth = depth;
+        while self.history.len() > depth {
+            self.history.pop_front();
+        }
+    }
+
+    /// Undoes the last `step` by returning to the state from before it, or returns false if there's no history left.
+    /// Only `step` records history, and counters like `cycles` aren't rewound.
+    pub fn step_back(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(snapshot) => {
+                self.restore(&snapshot).unwrap_or_else(|e| panic!("{}", e));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Enables or disables counting how often each kind of instruction is executed.
+    /// Profiling is disabled by default, since it slows down execution.
+    pub fn set_profiling(&mut self, is_profiling: bool) {
+        self.is_profiling = is_profiling;
+    }
+
+    /// The number of times each kind of instruction was executed while profiling, keyed by `Instruction::name`.
+    pub fn profile(&self) -> &BTreeMap<&'static str, u64> {
+        &self.profile
+    }
+
+    /// Enables or disables recording the anomalies described by `Anomaly`, which are also logged as warnings.
+    /// Tracking is disabled by default, since it's only useful while developing a program.
+    pub fn set_anomaly_tracking(&mut self, is_tracking_anomalies: bool) {
+        self.is_tracking_anomalies = is_tracking_anomalies;
+    }
+
+    /// Returns the anomalies recorded since the last call, oldest first.
+    pub fn take_anomalies(&mut self) -> Vec<Anomaly> {
+        core::mem::take(&mut self.anomalies)
+    }
+
+    fn record_anomaly(&mut self, anomaly: Anomaly) {
+        if self.is_tracking_anomalies {
+            warn!("Anomaly detected: {:?}", anomaly);
+            self.anomalies.push(anomaly);
+        }
+    }
+
+    /// Where `GotoOffsetted` jumps to, which depends on the jump quirk.
+    fn offset_jump_address(&self, address: u16) -> usize {
+        let offset_register = if self.quirks.is_jump_vx {
+            Register::new((address >> 8) as u8)
+        } else {
+            Register::first()
+        };
+        address as usize + self.registers.get_value(offset_register) as usize
+    }
+
+    /// Fails if the program counter is on a zero opcode that's configured to fail.
+    /// An instruction at the end of memory takes its remaining bytes from the start of memory.
+    fn decode(&self) -> Result<Instruction, MachineError> {
+        let pc = self.registers.program_counter;
+        let word = self.read_wrapped_words(pc);
+        if word[..2] == [0, 0] {
+            match self.config.zero_opcode {
+                ZeroOpcode::Halt => {}
+                ZeroOpcode::Error => return Err(MachineError::ZeroOpcode { pc }),
+                ZeroOpcode::Unknown => return Ok(Instruction::Unimplemented { opcode: 0x0000 }),
+            }
+        }
+        Ok(Instruction::new(&word, 0))
+    }
+
+    /// The two words starting at `address`, enough for any instruction, wrapping around the end of memory.
+    fn read_wrapped_words(&self, address: usize) -> [u8; 4] {
+        let memory = self.heap.get_all_bytes();
+        core::array::from_fn(|offset| memory[(address + offset) % memory.len()])
+    }
+
+    /// Where a skip from the instruction at `pc` lands, which is past `IStoreLongAddress`'s address word
+    /// when that's the instruction being skipped.
+    fn skip_address(&self, pc: usize) -> usize {
+        pc + 2 + Instruction::new(&self.read_wrapped_words(pc + 2), 0).size()
+    }
+
+    /// Executes an instruction decoded from the program counter.
+    /// `keys` holds a bit for each key which is held down, as built by `key_mask`.
+    fn execute(&mut self, instruction: Instruction, keys: u16) -> Result<Flow, MachineError> {
+        // Rejected opcodes are checked before anything else, so they leave the machine as it was.
+        if self.config.is_strict_opcodes {
+            let pc = self.registers.program_counter;
+            match instruction {
+                Instruction::Unimplemented { opcode } => return Err(MachineError::UnknownOpcode { opcode, pc }),
+                Instruction::SysCall { address } => return Err(MachineError::SysCall { address, pc }),
+                _ => {}
+            }
+        }
+
+        let keys = keys | self.latched_keys;
+        self.consumed_keys &= keys;
+        let keys = keys & !self.consumed_keys;
         let mut pc = self.registers.program_counter;
         let mut pause = false;
+        let mut end_frame = false;
+
+        trace!("{:#05x}: {}", pc, instruction);
+        if let Some(mut callback) = self.trace_callback.take() {
+            callback(self, pc, instruction);
+            self.trace_callback = Some(callback);
+        }
+
+        self.cycle_count += 1;
+        if self.is_profiling {
+            *self.profile.entry(instruction.name()).or_insert(0) += 1;
+        }
 
-        let instruction = Instruction::new(self.heap.get_all_bytes(), pc);
         match instruction {
             Instruction::Unimplemented {opcode} =>
                 warn!("Unimplemented instruction detected: {:#06x}", opcode),
+            Instruction::SysCall { .. } => {}
             Instruction::EndProgram =>
                 pause = true,
             Instruction::ClearScreen =>
                 self.display.clear(),
-            Instruction::ReturnSubroutine =>
-                pc = self.stack.pop() + 2,
+            Instruction::ReturnSubroutine => match self.stack.pop() {
+                Ok(address) => pc = address,
+                Err(e) => {
+                    self.record_anomaly(Anomaly::StackUnderflow { pc });
+                    return Err(e.into());
+                }
+            },
+            Instruction::ScrollDown { amount } =>
+                self.display.scroll_down(amount as usize),
+            Instruction::ScrollUp { amount } =>
+                self.display.scroll_up(amount as usize),
+            Instruction::ScrollRight =>
+                self.display.scroll_right(),
+            Instruction::ScrollLeft =>
+                self.display.scroll_left(),
+            Instruction::DisableHighRes =>
+                self.display.set_high_res(false),
+            Instruction::EnableHighRes =>
+                self.display.set_high_res(true),
+            Instruction::SelectPlanes { mask } =>
+                self.display.set_plane_mask(mask),
             Instruction::Goto { address } => {
                 if pc == address as usize {
                     pause = true;
@@ -54,35 +842,60 @@ impl Machine {
                 }
             }
             Instruction::CallSubroutine { address } => {
-                self.stack.push(pc);
+                // Push the return address, which is the instruction following this one.
+                if let Err(e) = self.stack.push(pc + 2) {
+                    self.record_anomaly(Anomaly::StackOverflow { pc });
+                    return Err(e.into());
+                }
                 pc = address as usize;
             }
             Instruction::SkipIfValueEq { register, value } =>
                 if self.registers.get_value(register) == value {
-                    pc += 4;
+                    pc = self.skip_address(pc);
                 },
             Instruction::SkipIfValueNe { register, value } =>
                 if self.registers.get_value(register) != value {
-                    pc += 4;
+                    pc = self.skip_address(pc);
                 },
             Instruction::SkipIfRegistersEq { register_x, register_y } =>
                 if self.registers.get_value(register_x) == self.registers.get_value(register_y) {
-                    pc += 4;
+                    pc = self.skip_address(pc);
                 },
             Instruction::RegisterValueStore { register, value } =>
                 self.registers.set_value(register, value),
-            Instruction::RegisterValueAdd { register, value } =>
-                self.registers.add_value(register, value),
+            Instruction::RegisterValueAdd { register, value } => {
+                if self.registers.get_value(register).checked_add(value).is_none() {
+                    self.record_anomaly(Anomaly::RegisterOverflow { pc, register });
+                }
+                self.registers.add_value(register, value);
+            }
             Instruction::RegistersCopy { register_to, register_from } =>
                 self.registers.copy_registers(register_to, register_from),
-            Instruction::RegistersOrEq { register_to, register_from } =>
-                self.registers.or_registers(register_to, register_from),
-            Instruction::RegistersAndEq { register_to, register_from } =>
-                self.registers.and_registers(register_to, register_from),
-            Instruction::RegistersXorEq { register_to, register_from } =>
-                self.registers.xor_registers(register_to, register_from),
-            Instruction::RegistersAdd { register_to, register_from } =>
-                self.registers.add_registers(register_to, register_from),
+            Instruction::RegistersOrEq { register_to, register_from } => {
+                self.registers.or_registers(register_to, register_from);
+                if self.quirks.is_reset_vf_on_logic {
+                    self.registers.set_flag(false);
+                }
+            }
+            Instruction::RegistersAndEq { register_to, register_from } => {
+                self.registers.and_registers(register_to, register_from);
+                if self.quirks.is_reset_vf_on_logic {
+                    self.registers.set_flag(false);
+                }
+            }
+            Instruction::RegistersXorEq { register_to, register_from } => {
+                self.registers.xor_registers(register_to, register_from);
+                if self.quirks.is_reset_vf_on_logic {
+                    self.registers.set_flag(false);
+                }
+            }
+            Instruction::RegistersAdd { register_to, register_from } => {
+                let value = self.registers.get_value(register_from);
+                if self.registers.get_value(register_to).checked_add(value).is_none() {
+                    self.record_anomaly(Anomaly::RegisterOverflow { pc, register: register_to });
+                }
+                self.registers.add_registers(register_to, register_from);
+            }
             Instruction::RegistersSub { register_to, register_from } =>
                 self.registers.sub_registers(register_to, register_from),
             Instruction::RegistersShiftRightEq { register_to, register_from } =>
@@ -93,13 +906,12 @@ impl Machine {
                 self.registers.shl_registers(register_to, register_from, self.quirks.is_lazy_shift),
             Instruction::SkipIfRegistersNe { register_x, register_y } =>
                 if self.registers.get_value(register_x) != self.registers.get_value(register_y) {
-                    pc += 4;
+                    pc = self.skip_address(pc);
                 },
-            Instruction::IStoreAddress { address } =>
+            Instruction::IStoreAddress { address } | Instruction::IStoreLongAddress { address } =>
                 self.registers.index = address as usize,
             Instruction::GotoOffsetted { address } => {
-                let offset = self.registers.get_value(Register::first()) as usize;
-                let adjusted_address = address as usize + offset;
+                let adjusted_address = self.offset_jump_address(address);
                 if pc == adjusted_address {
                     pause = true;
                 } else {
@@ -107,64 +919,1442 @@ impl Machine {
                 }
             }
             Instruction::RegisterStoreRandom { register, mask } =>
-                self.registers.set_value(register, fastrand::u8(..) & mask),
+                self.registers.set_value(register, self.rng.u8(..) & mask),
             Instruction::DrawSprite { register_x, register_y, sprite_height } => {
-                let sprite = self.heap.get_sprite(self.registers.index, sprite_height);
+                // A height of 0 draws a 16x16 sprite, which takes 2 bytes per row.
+                let is_large = sprite_height == 0;
+                let plane_len = if is_large { 32 } else { sprite_height };
+                // XO-CHIP sprites hold separate data for each selected plane.
+                let sprite_len = plane_len * self.display.plane_count() as u8;
+                let sprite = self.heap.get_sprite(self.registers.index, sprite_len)?;
                 let x = self.registers.get_value(register_x) as usize;
                 let y = self.registers.get_value(register_y) as usize;
-                let is_collision = self.display.render_sprite(x, y, sprite);
+                let (is_wrap_x, is_wrap_y) = (self.quirks.is_wrap_x, self.quirks.is_wrap_y);
+                let sprite_width = if is_large { display::LARGE_SPRITE_WIDTH } else { display::SPRITE_WIDTH };
+                let is_collision = self.display.render_sprite(x, y, sprite, sprite_width, is_wrap_x, is_wrap_y);
                 self.registers.set_flag(is_collision);
+                end_frame = self.quirks.is_display_wait;
+                if let Some(callback) = &mut self.draw_callback {
+                    let height = if is_large { 16 } else { sprite_height as usize };
+                    callback(DrawEvent { x, y, height, is_collision });
+                }
             }
             Instruction::SkipIfKeyOn { register } =>
-                if keys_pressed.contains(&self.registers.get_value(register)) {
-                    pc += 4;
+                if is_key_down(keys, self.registers.get_value(register)) {
+                    pc = self.skip_address(pc);
                 }
             Instruction::SkipIfKeyOff { register } =>
-                if !keys_pressed.contains(&self.registers.get_value(register)) {
-                    pc += 4;
+                if !is_key_down(keys, self.registers.get_value(register)) {
+                    pc = self.skip_address(pc);
                 }
+            Instruction::LoadAudioPattern => {
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+                pattern.copy_from_slice(self.heap.get_sprite(self.registers.index, AUDIO_PATTERN_SIZE as u8)?);
+                self.audio_pattern = Some(pattern);
+            }
+            Instruction::SetAudioPitch { register } =>
+                self.audio_pitch = self.registers.get_value(register),
             Instruction::DelayTimerToRegister { register } =>
                 self.registers.set_value(register, self.timers.delay),
             Instruction::WaitForAnyKey { register } => {
-                if keys_pressed.is_empty() {
-                    pause = true;
-                } else {
-                    pause = false;
-                    self.registers.set_value(register, keys_pressed[0]);
+                // Like the original hardware, wait for a key to be both pressed and released.
+                match self.pressed_key {
+                    Some(key) if !is_key_down(keys, key) => {
+                        self.registers.set_value(register, key);
+                        self.pressed_key = None;
+                        self.consumed_keys |= keys;
+                    }
+                    Some(_) => pause = true,
+                    None => {
+                        // When several keys are held down, the lowest one wins.
+                        self.pressed_key = (keys != 0).then(|| keys.trailing_zeros() as u8);
+                        pause = true;
+                    }
                 }
             }
             Instruction::RegisterToDelayTimer { register } =>
                 self.timers.delay = self.registers.get_value(register),
             Instruction::RegisterToSoundTimer { register } =>
                 self.timers.sound = self.registers.get_value(register),
-            Instruction::IAddOffset { register } =>
-                self.registers.index += self.registers.get_value(register) as usize,
+            Instruction::IAddOffset { register } => {
+                let index = self.registers.index + self.registers.get_value(register) as usize;
+                if self.quirks.is_i_overflow_sets_vf {
+                    self.registers.set_flag(index > 0xFFF);
+                }
+                if index >= self.config.memory_size {
+                    self.record_anomaly(Anomaly::IndexOverflow { pc });
+                }
+                self.registers.index = index % self.config.memory_size;
+            }
             Instruction::IStoreDigitAddress { register } => {
                 let digit = self.registers.get_value(register) as usize;
                 self.registers.index = heap::OFFSET_FONT + (digit * 5);
             }
+            Instruction::IStoreBigDigitAddress { register } => {
+                let digit = self.registers.get_value(register) as usize;
+                self.registers.index = heap::OFFSET_BIG_FONT + (digit * 10);
+            }
             Instruction::HexToDecimal { register } =>
-                self.heap.set_as_decimal(self.registers.index, self.registers.get_value(register)),
+                self.heap.set_as_decimal(self.registers.index, self.registers.get_value(register))?,
             Instruction::RegistersDump { max_register } => {
-                self.heap.set_bytes(self.registers.index, self.registers.dump(max_register));
-                if !self.quirks.is_static_dump_index {
-                    self.registers.index += max_register.idx() + 1;
-                }
+                self.heap.set_bytes(self.registers.index, self.registers.dump(max_register))?;
+                self.increment_dump_index(max_register);
             }
             Instruction::RegistersLoad { max_register } => {
-                self.registers.load(self.heap.get_bytes(self.registers.index, max_register.idx()));
-                if !self.quirks.is_static_dump_index {
-                    self.registers.index += max_register.idx() + 1;
-                }
+                self.heap.check_bounds(self.registers.index, max_register.idx() + 1)?;
+                self.registers.load(self.heap.get_bytes(self.registers.index, max_register.idx() + 1));
+                self.increment_dump_index(max_register);
+            }
+            Instruction::SaveFlags { max_register } => {
+                // Registers past V7 have no flag to go into, so they're left out.
+                let count = (max_register.idx() + 1).min(FLAG_COUNT);
+                self.flags[..count].copy_from_slice(&self.registers.snapshot()[..count]);
+            }
+            Instruction::LoadFlags { max_register } => {
+                let count = (max_register.idx() + 1).min(FLAG_COUNT);
+                self.registers.load(&self.flags[..count]);
             }
         }
 
-        self.timers.tick();
-
         if !pause && pc == self.registers.program_counter {
-            // By default, increment the program counter by two bytes (one word length).
-            pc += 2;
+            // By default, move the program counter to the following instruction.
+            pc += instruction.size();
         }
-        self.registers.program_counter = pc;
+        // The program counter wraps around the address space, rather than running off the end of memory.
+        self.registers.program_counter = pc % self.config.memory_size;
+        Ok(if pause {
+            Flow::Pause
+        } else if end_frame {
+            Flow::EndFrame
+        } else {
+            Flow::Continue
+        })
+    }
+
+    /// Moves `I` after registers `V0` to `max_register` have been dumped or loaded, depending on the quirk in use.
+    fn increment_dump_index(&mut self, max_register: Register) {
+        match self.quirks.dump_index {
+            DumpIndex::IncrementByXPlus1 => self.registers.index += max_register.idx() + 1,
+            DumpIndex::IncrementByX => self.registers.index += max_register.idx(),
+            DumpIndex::Unchanged => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::hash::{Hash, Hasher};
+    use std::rc::Rc;
+    use super::*;
+    use crate::InputLog;
+
+    #[test]
+    pub fn test_reset() {
+        let program = vec![
+            0x60, 0x05, // V0 = 0x05
+            0xA1, 0x23, // I = 0x123
+            0xF0, 0x15, // delay = V0
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        for _ in 0..3 {
+            machine.tick(vec![]);
+        }
+        assert_eq!(machine.registers.get_value(Register::first()), 0x05);
+        assert_eq!(machine.registers.index, 0x123);
+        assert_eq!(machine.registers.program_counter, heap::OFFSET_DATA + 6);
+
+        machine.reset();
+        assert_eq!(machine.registers.get_value(Register::first()), 0);
+        assert_eq!(machine.registers.index, 0);
+        assert_eq!(machine.registers.program_counter, heap::OFFSET_DATA);
+        assert_eq!(machine.timers.delay, 0);
+
+        machine.tick(vec![]);
+        assert_eq!(machine.registers.get_value(Register::first()), 0x05);
+    }
+
+    #[test]
+    pub fn test_tick_many() {
+        let program = vec![
+            0x60, 0x05, // V0 = 0x05
+            0xF0, 0x15, // delay = V0
+            0x61, 0x01, // V1 = 0x01
+            0x62, 0x02, // V2 = 0x02
+            0xF3, 0x0A, // V3 = wait for key
+            0x64, 0x04, // V4 = 0x04
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+
+        machine.tick_many(&[], 3).unwrap();
+        assert_eq!(machine.registers.program_counter, heap::OFFSET_DATA + 6);
+        assert_eq!(machine.timers.delay, 4, "Timers should only tick once per frame");
+
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(machine.registers.program_counter, heap::OFFSET_DATA + 8, "Frame should end on key wait");
+        assert_eq!(machine.registers.get_value(Register::new(0x4)), 0);
+
+        machine.tick_many(&[0xA], 10).unwrap();
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(machine.registers.get_value(Register::new(0x3)), 0xA);
+        assert_eq!(machine.registers.get_value(Register::new(0x4)), 0x04);
+    }
+
+    #[test]
+    pub fn test_tick_many_returns_on_key_wait() {
+        let program = vec![
+            0x61, 0x01, // V1 = 0x01
+            0xF3, 0x0A, // V3 = wait for key
+            0x12, 0x00, // jump to 0x200
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+
+        assert_eq!(machine.tick_many(&[], 100), Ok(2), "Frame should count the key wait and end there");
+        for _ in 0..5 {
+            assert_eq!(machine.tick_many(&[], 100), Ok(1), "Frames blocked on a key should only run the key wait");
+        }
+        assert_eq!(machine.cycles(), 7);
+
+        machine.tick_many(&[0x2], 100).unwrap();
+        assert_eq!(machine.tick_many(&[], 3), Ok(3), "Frame should use its whole budget once the key is released");
+    }
+
+    #[test]
+    pub fn test_set_keys() {
+        let program = vec![
+            0x60, 0x05, // V0 = 0x05
+            0xE0, 0x9E, // skip next if V0 is pressed
+            0x61, 0x01, // V1 = 0x01
+            0xE0, 0x9E, // skip next if V0 is pressed
+            0x62, 0x02, // V2 = 0x02
+            0x12, 0x00, // jump to 0x200
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.set_keys(&[0x5]);
+        for _ in 0..4 {
+            machine.step(&[]).unwrap();
+        }
+        assert_eq!(machine.register(Register::new(0x1)), 0, "Latched keys should be seen by every step");
+        assert_eq!(machine.register(Register::new(0x2)), 0);
+
+        // Keys passed to a step are held on top of the latched ones, but only for that step.
+        machine.set_keys(&[]);
+        for _ in 0..3 {
+            machine.step(&[]).unwrap();
+        }
+        assert_eq!(machine.register(Register::new(0x1)), 1);
+        assert_eq!(machine.step(&[0x5]).unwrap().pc_after, 0x20A);
+        for _ in 0..6 {
+            machine.step(&[]).unwrap();
+        }
+        assert_eq!(machine.register(Register::new(0x2)), 2);
+    }
+
+    #[test]
+    pub fn test_with_seed() {
+        let program = vec![
+            0xC0, 0xFF, // V0 = rand() & 0xFF
+            0xC1, 0xFF, // V1 = rand() & 0xFF
+            0xC2, 0x0F, // V2 = rand() & 0x0F
+        ];
+        let mut machine_a = Machine::with_seed(program.clone(), Quirks::inactive(), 0xC8);
+        let mut machine_b = Machine::with_seed(program, Quirks::inactive(), 0xC8);
+        for _ in 0..3 {
+            machine_a.tick(vec![]);
+            machine_b.tick(vec![]);
+        }
+
+        for i in 0..3 {
+            let register = Register::new(i);
+            assert_eq!(machine_a.registers.get_value(register), machine_b.registers.get_value(register));
+        }
+        assert_eq!(machine_a.registers.get_value(Register::new(0x2)) & 0xF0, 0);
+    }
+
+    #[test]
+    pub fn test_input_log_replay() {
+        let program = vec![
+            0xC0, 0x3F, // V0 = random & 0x3F
+            0xC1, 0x1F, // V1 = random & 0x1F
+            0xA2, 0x0E, // I = 0x20E
+            0xE2, 0xA1, // skip if key V2 is up
+            0xD0, 0x11, // draw(V0, V1, 1)
+            0x72, 0x01, // V2 += 0x01
+            0x12, 0x00, // jump to 0x200
+            0xC0,       // sprite data
+        ];
+        let framebuffer_hash = |machine: &Machine| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            machine.framebuffer().hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut machine = Machine::with_seed(&program, Quirks::inactive(), 0x5EED);
+        let mut log = InputLog::new(0x5EED);
+        let mut rng = fastrand::Rng::with_seed(0x1234);
+        for _ in 0..120 {
+            let keys: Vec<u8> = (0..16).filter(|_| rng.u8(..4) == 0).collect();
+            log.record(&keys);
+            machine.tick_many(&keys, 10).unwrap();
+        }
+
+        let log = InputLog::parse(&log.to_text()).unwrap();
+        let mut replayed = Machine::new(&program, Quirks::inactive());
+        replayed.set_seed(log.seed());
+        for frame in 0..log.frame_count() {
+            replayed.tick_many(&log.keys_at(frame), 10).unwrap();
+        }
+        assert_ne!(framebuffer_hash(&machine), framebuffer_hash(&Machine::new(&program, Quirks::inactive())));
+        assert_eq!(framebuffer_hash(&replayed), framebuffer_hash(&machine));
+        assert_eq!(replayed.registers(), machine.registers());
+    }
+
+    #[test]
+    pub fn test_step() {
+        let program = vec![
+            0x6A, 0x42, // VA = 0x42
+            0x22, 0x06, // call 0x206
+            0x00, 0x00, // end
+            0xFA, 0x18, // sound = VA
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+
+        let executed = machine.step(&[]).unwrap();
+        assert_eq!(executed, ExecutedInstruction {
+            instruction: Instruction::RegisterValueStore { register: Register::new(0xA), value: 0x42 },
+            pc_before: 0x200,
+            pc_after: 0x202,
+        });
+        assert_eq!(machine.register(Register::new(0xA)), 0x42);
+
+        let executed = machine.step(&[]).unwrap();
+        assert_eq!(executed.instruction, Instruction::CallSubroutine { address: 0x206 });
+        assert_eq!((executed.pc_before, executed.pc_after), (0x202, 0x206));
+        assert_eq!(machine.stack_depth(), 1);
+
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.sound_timer(), 0x42);
+        assert_eq!(machine.delay_timer(), 0);
+        assert_eq!(machine.program_counter(), 0x208);
+        assert_eq!(machine.index(), 0);
+    }
+
+    #[test]
+    pub fn test_step_back() {
+        let program = vec![
+            0x60, 0x05, // V0 = 0x05
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x33, // store BCD of V0 at I
+            0x22, 0x0A, // call 0x20A
+            0x12, 0x08, // jump to self
+            0xD0, 0x05, // draw(V0, V0, 5)
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.step(&[]).unwrap();
+        assert!(!machine.step_back(), "History should be off by default");
+
+        machine.set_history_depth(3);
+        let mut states = vec![machine.snapshot()];
+        for _ in 0..5 {
+            machine.step(&[]).unwrap();
+            states.push(machine.snapshot());
+        }
+        assert!(machine.framebuffer().contains(&1));
+
+        for state in states.iter().rev().skip(1).take(3) {
+            assert!(machine.step_back());
+            assert_eq!(&machine.snapshot(), state, "Stepping back should return to the exact prior state");
+        }
+        assert!(!machine.step_back(), "Only the last 3 steps should be kept");
+        assert_eq!(machine.program_counter(), 0x206);
+
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.snapshot(), states[3], "Stepping forward again should replay the same step");
+    }
+
+    #[test]
+    pub fn test_set_registers() {
+        let mut machine = Machine::new_empty(Quirks::inactive());
+        machine.write_memory(0x300, &[0xD5, 0x55]).unwrap(); // draw(V5, V5, 5)
+        machine.set_program_counter(0x300).unwrap();
+        machine.set_register(Register::new(0x5), 0x02);
+        machine.set_index(heap::OFFSET_FONT + 5).unwrap(); // the sprite for 1
+        assert_eq!(machine.register(Register::new(0x5)), 0x02);
+        assert_eq!(machine.index(), heap::OFFSET_FONT + 5);
+
+        machine.step(&[]).unwrap();
+        let ascii = machine.to_ascii();
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(&lines[2][..8], "    #   ");
+        assert_eq!(&lines[3][..8], "   ##   ");
+        assert_eq!(&lines[6][..8], "   ###  ");
+
+        assert_eq!(machine.set_index(0x1000), Err(MachineError::MemoryOutOfBounds { address: 0x1000 }));
+        assert_eq!(machine.set_program_counter(0x1000), Err(MachineError::MemoryOutOfBounds { address: 0x1000 }));
+        assert_eq!((machine.program_counter(), machine.index()), (0x302, heap::OFFSET_FONT + 5), "Failed sets should change nothing");
+    }
+
+    #[test]
+    pub fn test_peek_instruction() {
+        let program = vec![
+            0x6A, 0x42, // VA = 0x42
+            0x12, 0x00, // jump to 0x200
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        for _ in 0..4 {
+            let peeked = machine.peek_instruction().unwrap();
+            assert_eq!(machine.peek_instruction(), Ok(peeked), "Peeking shouldn't change the machine");
+            assert_eq!(machine.step(&[]).unwrap().instruction, peeked);
+        }
+
+        machine.write_memory(0x200, &[0x00, 0x00]).unwrap();
+        machine.config.zero_opcode = ZeroOpcode::Error;
+        assert_eq!(machine.peek_instruction(), Err(MachineError::ZeroOpcode { pc: 0x200 }));
+    }
+
+    #[test]
+    pub fn test_analyze() {
+        let program = vec![
+            0xA2, 0x0A, // I = 0x20A
+            0x22, 0x08, // call 0x208
+            0x12, 0x04, // jump to 0x204
+            0x00, 0x00, // end
+            0x00, 0xEE, // return
+            0xF0, 0x90, // sprite data, which decodes as an unknown opcode
+        ];
+        let report = Machine::new(program, Quirks::inactive()).analyze();
+        assert_eq!(report, RomReport {
+            instruction_count: 4,
+            unimplemented_count: 0,
+            program_size: 12,
+            highest_address: Some(0x20A),
+            stray_jump_count: 0,
+        });
+        assert!(!report.is_suspicious());
+
+        let programs: [&[u8]; 3] = [
+            include_bytes!("../tests/roms/PONG.ch8"),
+            include_bytes!("../tests/roms/BRIX.ch8"),
+            include_bytes!("../tests/roms/INVADERS.ch8"),
+        ];
+        for program in programs {
+            let report = Machine::new(program, Quirks::inactive()).analyze();
+            assert!(!report.is_suspicious(), "Real programs shouldn't be suspicious: {:?}", report);
+        }
+        let stray_jumps = |stray_jump_count| RomReport {
+            instruction_count: 100,
+            unimplemented_count: 0,
+            program_size: 200,
+            highest_address: Some(0x2C6),
+            stray_jump_count,
+        };
+        assert!(!stray_jumps(1).is_suspicious(), "A single stray jump among plenty of code shouldn't be suspicious");
+        assert!(stray_jumps(10).is_suspicious());
+        for seed in 0..20 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let noise: Vec<u8> = (0..2048).map(|_| rng.u8(..)).collect();
+            let report = Machine::new(noise, Quirks::inactive()).analyze();
+            assert!(report.is_suspicious(), "Random bytes should be suspicious: {:?}", report);
+        }
+
+        assert!(Machine::new_empty(Quirks::inactive()).analyze().is_suspicious(), "Empty programs should be suspicious");
+    }
+
+    #[test]
+    pub fn test_breakpoints() {
+        let program = vec![
+            0x60, 0x01, // V0 = 0x01
+            0x61, 0x02, // V1 = 0x02
+            0x62, 0x03, // V2 = 0x03
+            0xF3, 0x0A, // V3 = wait for key
+            0x12, 0x08, // jump to self
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.add_breakpoint(0x202);
+        machine.add_breakpoint(0x204);
+
+        assert_eq!(machine.run_until_breakpoint(&[]), Ok(StopReason::Breakpoint { address: 0x202 }));
+        assert_eq!(machine.register(Register::new(0x1)), 0);
+        assert_eq!(machine.run_until_breakpoint(&[]), Ok(StopReason::Breakpoint { address: 0x204 }));
+
+        machine.remove_breakpoint(0x204);
+        machine.reset();
+        assert_eq!(machine.run_until_breakpoint(&[]), Ok(StopReason::Breakpoint { address: 0x202 }));
+        assert_eq!(machine.run_until_breakpoint(&[]), Ok(StopReason::WaitingForKey));
+        assert_eq!(machine.program_counter(), 0x206);
+        assert_eq!(machine.run_until_breakpoint(&[0x5]), Ok(StopReason::WaitingForKey));
+        assert_eq!(machine.run_until_breakpoint(&[]), Ok(StopReason::EndOfProgram));
+        assert_eq!(machine.register(Register::new(0x3)), 0x5);
+    }
+
+    #[test]
+    pub fn test_disassemble_range() {
+        let program = vec![
+            0x60, 0x05, // V0 = 0x05
+            0xD0, 0x15, // draw(V0, V1, 5)
+            0x12, 0x04, // jump to self
+        ];
+        let machine = Machine::new(program, Quirks::inactive());
+
+        let listing = machine.disassemble_range(0x200, 0x205);
+        assert_eq!(listing.len(), 2, "Trailing single byte should be skipped");
+        assert_eq!(listing[0], (0x200, Instruction::RegisterValueStore { register: Register::first(), value: 0x05 }, "LD V0, 0x05".to_string()));
+        assert_eq!(listing[1].2, "DRW V0, V1, 5");
+
+        let listing = machine.disassemble_range(0x204, 0x206);
+        assert_eq!(listing[0].2, "JP 0x204");
+    }
+
+    #[test]
+    pub fn test_i_store_long_address() {
+        let mut machine = Machine::new(vec![0xF0, 0x00, 0x0A, 0xBC], Quirks::xochip()); // I = 0x0ABC
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.index(), 0x0ABC);
+        assert_eq!(machine.program_counter(), 0x204, "Both words should be stepped over");
+
+        let program = vec![
+            0x30, 0x00, // 0x200: skip if V0 == 0x00
+            0xF0, 0x00, // 0x202: I = 0x0ABC
+            0x0A, 0xBC,
+            0x61, 0x01, // 0x206: V1 = 0x01
+        ];
+        let mut machine = Machine::new(program, Quirks::xochip());
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.program_counter(), 0x206, "Skips should step over the address word too");
+        assert_eq!(machine.index(), 0);
+
+        let listing = machine.disassemble_range(0x200, 0x208);
+        let addresses: Vec<usize> = listing.iter().map(|(address, _, _)| *address).collect();
+        assert_eq!(addresses, [0x200, 0x202, 0x206]);
+        assert_eq!(listing[1].2, "LD I, LONG 0x0ABC");
+    }
+
+    #[test]
+    pub fn test_nested_subroutines() {
+        let program = vec![
+            0x22, 0x06, // 0x200: call 0x206
+            0x60, 0x01, // 0x202: V0 = 0x01
+            0x12, 0x04, // 0x204: jump to self
+            0x22, 0x0C, // 0x206: call 0x20C
+            0x61, 0x02, // 0x208: V1 = 0x02
+            0x00, 0xEE, // 0x20A: return
+            0x62, 0x03, // 0x20C: V2 = 0x03
+            0x00, 0xEE, // 0x20E: return
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+
+        machine.tick_many(&[], 3).unwrap();
+        assert_eq!(machine.call_stack(), &[0x202, 0x208]);
+        assert_eq!(machine.program_counter(), 0x20E);
+
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.program_counter(), 0x208);
+        assert_eq!(machine.call_stack(), &[0x202]);
+
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(machine.program_counter(), 0x204);
+        assert_eq!(machine.stack_depth(), 0);
+        for (i, value) in [0x01, 0x02, 0x03].into_iter().enumerate() {
+            assert_eq!(machine.register(Register::new(i as u8)), value);
+        }
+    }
+
+    #[test]
+    pub fn test_large_sprite_and_scroll_up() {
+        let mut program = vec![
+            0x00, 0xFF, // enable high res
+            0xA2, 0x0E, // I = 0x20E
+            0x60, 0x02, // V0 = 0x02
+            0xD0, 0x00, // draw(V0, V0, 16x16)
+            0x00, 0xD2, // scroll up 2
+            0xD0, 0x00, // draw(V0, V0, 16x16)
+            0x12, 0x0C, // jump to self
+        ];
+        program.extend([0xFF; 32]);
+        let mut machine = Machine::new(program, Quirks::superchip());
+
+        machine.tick_many(&[], 4).unwrap();
+        let lit = |machine: &Machine| machine.framebuffer().iter().filter(|&&pixel| pixel != 0).count();
+        assert_eq!(lit(&machine), 16 * 16);
+        assert_eq!(machine.framebuffer()[128 * 2 + 2], 1);
+        assert_eq!(machine.register(Register::flag()), 0);
+
+        machine.tick_many(&[], 2).unwrap();
+        // The overlapping rows are erased, leaving the two rows on either side of the overlap.
+        assert_eq!(lit(&machine), 16 * 4);
+        assert_eq!(machine.framebuffer()[2], 1);
+        assert_eq!(machine.framebuffer()[128 * 17 + 2], 1);
+        assert_eq!(machine.framebuffer()[128 * 2 + 2], 0);
+        assert_eq!(machine.register(Register::flag()), 1);
+    }
+
+    #[test]
+    pub fn test_audio_pattern() {
+        let mut program = vec![
+            0xA2, 0x08, // I = 0x208
+            0xF0, 0x02, // load audio pattern
+            0x60, 0x70, // V0 = 0x70
+            0xF0, 0x3A, // pitch = V0
+        ];
+        program.extend(0..16);
+        let mut machine = Machine::new(program, Quirks::xochip());
+        assert_eq!(machine.audio_pattern(), None);
+        #[cfg(feature = "std")]
+        assert_eq!(machine.audio_playback_rate(), 4000.0);
+
+        machine.tick_many(&[], 4).unwrap();
+        let expected: Vec<u8> = (0..16).collect();
+        assert_eq!(machine.audio_pattern().map(|pattern| pattern.to_vec()), Some(expected));
+        assert_eq!(machine.audio_pitch(), 0x70);
+        #[cfg(feature = "std")]
+        assert_eq!(machine.audio_playback_rate(), 8000.0);
+
+        machine.reset();
+        assert_eq!(machine.audio_pattern(), None);
+    }
+
+    #[test]
+    pub fn test_select_planes() {
+        let program = vec![
+            0xF3, 0x01, // select planes 1 and 2
+            0xA2, 0x0A, // I = 0x20A
+            0xD0, 0x01, // draw(V0, V0, 1)
+            0xD0, 0x01, // draw(V0, V0, 1)
+            0x12, 0x08, // jump to self
+            0xC0, 0xA0, // sprite rows for planes 1 and 2
+        ];
+        let mut machine = Machine::new(program, Quirks::xochip());
+        machine.tick_many(&[], 3).unwrap();
+        assert_eq!(machine.framebuffer()[..4], [3, 1, 2, 0]);
+        assert_eq!(machine.register(Register::flag()), 0);
+
+        machine.tick_many(&[], 1).unwrap();
+        assert!(!machine.framebuffer().contains(&3));
+        assert_eq!(machine.register(Register::flag()), 1);
+    }
+
+    #[test]
+    pub fn test_framebuffer() {
+        let program = vec![
+            0x60, 0x0A, // V0 = 0x0A
+            0xF0, 0x29, // I = sprite for digit V0
+            0x61, 0x02, // V1 = 0x02
+            0xD1, 0x15, // draw(V1, V1, 5)
+            0x12, 0x08, // jump to self
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.tick_many(&[], 10).unwrap();
+
+        let framebuffer = machine.framebuffer();
+        assert_eq!(framebuffer.len(), 64 * 32);
+        let row = |y: usize| -> Vec<u8> { framebuffer[(64 * y)..(64 * y + 8)].to_vec() };
+        assert_eq!(row(1), vec![0; 8]);
+        assert_eq!(row(2), vec![0, 0, 1, 1, 1, 1, 0, 0]);
+        assert_eq!(row(3), vec![0, 0, 1, 0, 0, 1, 0, 0]);
+        assert_eq!(framebuffer.iter().filter(|&&pixel| pixel != 0).count(), 14);
+
+        let mut client = Machine::new_empty(Quirks::inactive());
+        client.load_serialized_display(&machine.serialize_display()).unwrap();
+        assert_eq!(client.framebuffer(), machine.framebuffer());
+        assert_eq!(client.load_serialized_display(&[0]), Err(MachineError::InvalidDisplayData { len: 1 }));
+    }
+
+    #[test]
+    pub fn test_reset_vf_on_logic() {
+        let program = vec![
+            0x6F, 0x07, // VF = 0x07
+            0x60, 0x30, // V0 = 0x30
+            0x61, 0x03, // V1 = 0x03
+            0x80, 0x11, // V0 |= V1
+        ];
+
+        let mut machine = Machine::new(program.clone(), Quirks::inactive());
+        machine.tick_many(&[], 4).unwrap();
+        assert_eq!(machine.register(Register::first()), 0x33);
+        assert_eq!(machine.register(Register::flag()), 0x07);
+
+        let mut quirks = Quirks::inactive();
+        quirks.is_reset_vf_on_logic = true;
+        let mut machine = Machine::new(program, quirks);
+        machine.tick_many(&[], 4).unwrap();
+        assert_eq!(machine.register(Register::first()), 0x33);
+        assert_eq!(machine.register(Register::flag()), 0);
+    }
+
+    #[test]
+    pub fn test_jump_vx() {
+        let program = vec![
+            0x60, 0x04, // V0 = 0x04
+            0x62, 0x08, // V2 = 0x08
+            0xB2, 0x20, // jump to 0x220 + offset
+        ];
+
+        let mut machine = Machine::new(program.clone(), Quirks::inactive());
+        machine.tick_many(&[], 3).unwrap();
+        assert_eq!(machine.program_counter(), 0x224, "BNNN should offset by V0");
+
+        let mut quirks = Quirks::inactive();
+        quirks.is_jump_vx = true;
+        let mut machine = Machine::new(program, quirks);
+        machine.tick_many(&[], 3).unwrap();
+        assert_eq!(machine.program_counter(), 0x228, "BXNN should offset by V2");
+    }
+
+    #[test]
+    pub fn test_display_wait() {
+        let program = vec![
+            0xD0, 0x01, // draw(V0, V0, 1)
+            0x61, 0x01, // V1 = 0x01
+            0xD0, 0x01, // draw(V0, V0, 1)
+            0x62, 0x02, // V2 = 0x02
+        ];
+
+        let mut machine = Machine::new(program.clone(), Quirks::inactive());
+        machine.tick_many(&[], 4).unwrap();
+        assert_eq!(machine.program_counter(), 0x208);
+
+        let mut quirks = Quirks::inactive();
+        quirks.is_display_wait = true;
+        let mut machine = Machine::new(program, quirks);
+        machine.tick_many(&[], 4).unwrap();
+        assert_eq!(machine.program_counter(), 0x202, "Frame should end after the first draw");
+        machine.tick_many(&[], 4).unwrap();
+        assert_eq!(machine.program_counter(), 0x206);
+        assert_eq!(machine.register(Register::new(0x1)), 0x01);
+        assert_eq!(machine.register(Register::new(0x2)), 0);
+    }
+
+    #[test]
+    pub fn test_trace_callback() {
+        let program = vec![
+            0x60, 0x05, // V0 = 0x05
+            0x22, 0x06, // call 0x206
+            0x12, 0x04, // jump to self
+            0x00, 0xEE, // return
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        let traced = Rc::new(RefCell::new(Vec::new()));
+        let traced_by_callback = Rc::clone(&traced);
+        machine.set_trace_callback(Box::new(move |machine, pc, instruction| {
+            traced_by_callback.borrow_mut().push((pc, instruction, machine.register(Register::first())));
+        }));
+
+        machine.tick_many(&[], 5).unwrap();
+        assert_eq!(*traced.borrow(), vec![
+            (0x200, Instruction::RegisterValueStore { register: Register::first(), value: 0x05 }, 0x00),
+            (0x202, Instruction::CallSubroutine { address: 0x206 }, 0x05),
+            (0x206, Instruction::ReturnSubroutine, 0x05),
+            (0x204, Instruction::Goto { address: 0x204 }, 0x05),
+        ], "The callback should see the machine from before each instruction");
+    }
+
+    #[test]
+    pub fn test_draw_callback() {
+        let program = vec![
+            0xA2, 0x0C, // I = 0x20C
+            0x60, 0x04, // V0 = 0x04
+            0xD0, 0x02, // draw 2 rows at (V0, V0)
+            0x61, 0x05, // V1 = 0x05
+            0xD1, 0x12, // draw 2 rows at (V1, V1), overlapping the first sprite
+            0x12, 0x0A, // jump to self
+            0xFF, 0xFF, // sprite data
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events_by_callback = Rc::clone(&events);
+        machine.set_draw_callback(Box::new(move |event| events_by_callback.borrow_mut().push(event)));
+
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(*events.borrow(), vec![
+            DrawEvent { x: 4, y: 4, height: 2, is_collision: false },
+            DrawEvent { x: 5, y: 5, height: 2, is_collision: true },
+        ]);
+        assert_eq!(machine.register(Register::flag()), 1);
+    }
+
+    #[test]
+    pub fn test_timer_callback() {
+        let program = vec![
+            0x60, 0x01, // V0 = 0x01
+            0x61, 0x02, // V1 = 0x02
+            0xF0, 0x15, // delay timer = V0
+            0xF1, 0x18, // sound timer = V1
+            0x12, 0x08, // jump to self
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        let expired = Rc::new(RefCell::new(Vec::new()));
+        let expired_by_callback = Rc::clone(&expired);
+        machine.set_timer_callback(Box::new(move |timer| expired_by_callback.borrow_mut().push(timer)));
+
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(*expired.borrow(), vec![TimerKind::Delay]);
+        machine.tick_many(&[], 10).unwrap();
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(*expired.borrow(), vec![TimerKind::Delay, TimerKind::Sound], "Timers at zero shouldn't fire again");
+    }
+
+    #[test]
+    pub fn test_anomalies() {
+        let program = vec![
+            0x60, 0xFF, // 0x200: V0 = 0xFF
+            0x70, 0x02, // 0x202: V0 += 0x02, which wraps around
+            0x00, 0xEE, // 0x204: return, without a subroutine to return from
+        ];
+        let mut machine = Machine::new(program.clone(), Quirks::inactive());
+        assert!(machine.tick_many(&[], 3).is_err());
+        assert_eq!(machine.take_anomalies(), [], "Anomalies shouldn't be recorded unless tracking is enabled");
+
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.set_anomaly_tracking(true);
+        assert_eq!(machine.tick_many(&[], 3), Err(MachineError::StackUnderflow));
+        assert_eq!(machine.take_anomalies(), [
+            Anomaly::RegisterOverflow { pc: 0x202, register: Register::first() },
+            Anomaly::StackUnderflow { pc: 0x204 },
+        ]);
+        assert_eq!(machine.take_anomalies(), [], "Taking anomalies should drain them");
+    }
+
+    #[test]
+    pub fn test_cycles_and_profile() {
+        let program = vec![
+            0x60, 0x00, // V0 = 0x00
+            0x70, 0x01, // V0 += 0x01
+            0x30, 0x05, // skip if V0 == 0x05
+            0x12, 0x02, // jump to 0x202
+            0x12, 0x08, // jump to self
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.set_profiling(true);
+        machine.tick_many(&[], 100).unwrap();
+
+        // 1 store, 5 loops of add and skip, 4 jumps back, then the final self-jump
+        assert_eq!(machine.cycles(), 1 + 5 * 2 + 4 + 1);
+        let profile = machine.profile();
+        assert_eq!(profile["RegisterValueStore"], 1);
+        assert_eq!(profile["RegisterValueAdd"], 5);
+        assert_eq!(profile["SkipIfValueEq"], 5);
+        assert_eq!(profile["Goto"], 5);
+        assert_eq!(profile.len(), 4);
+
+        machine.reset();
+        assert_eq!(machine.cycles(), 0);
+        assert!(machine.profile().is_empty());
+    }
+
+    #[test]
+    pub fn test_unknown_opcode() {
+        let program = vec![
+            0x5A, 0xB1, // invalid
+            0x60, 0x01, // V0 = 0x01
+        ];
+
+        let mut machine = Machine::new(program.clone(), Quirks::inactive());
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.register(Register::first()), 0x01, "Lenient mode should skip unknown opcodes");
+
+        let config = MachineConfig { is_strict_opcodes: true, ..MachineConfig::default() };
+        let mut machine = Machine::with_config(program, Quirks::inactive(), config);
+        let traced = Rc::new(RefCell::new(0));
+        let traced_in_callback = Rc::clone(&traced);
+        machine.set_trace_callback(Box::new(move |_, _, _| *traced_in_callback.borrow_mut() += 1));
+        assert_eq!(machine.tick_many(&[], 2), Err(MachineError::UnknownOpcode { opcode: 0x5AB1, pc: 0x200 }));
+        assert_eq!(machine.program_counter(), 0x200);
+        assert_eq!(machine.register(Register::first()), 0);
+        assert_eq!(machine.cycles(), 0, "Rejected opcodes shouldn't count as executed");
+        assert_eq!(*traced.borrow(), 0, "Rejected opcodes shouldn't be traced");
+    }
+
+    #[test]
+    pub fn test_sys_call() {
+        let program = vec![
+            0x01, 0x23, // call machine code at 0x123
+            0x60, 0x01, // V0 = 0x01
+        ];
+        let mut machine = Machine::new(program.clone(), Quirks::inactive());
+        assert_eq!(machine.peek_instruction(), Ok(Instruction::SysCall { address: 0x123 }));
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.register(Register::first()), 0x01, "SYS should be skipped over");
+        assert_eq!(machine.stack_depth(), 0);
+
+        let config = MachineConfig { is_strict_opcodes: true, ..MachineConfig::default() };
+        let mut machine = Machine::with_config(program, Quirks::inactive(), config);
+        assert_eq!(machine.tick_many(&[], 2), Err(MachineError::SysCall { address: 0x123, pc: 0x200 }));
+        assert_eq!(machine.program_counter(), 0x200);
+    }
+
+    #[test]
+    pub fn test_draw_out_of_bounds() {
+        let program = vec![
+            0xD0, 0x0F, // draw(V0, V0, 15)
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.registers.index = 0xFFA;
+        assert_eq!(machine.try_tick(&[]), Err(MachineError::MemoryOutOfBounds { address: 0x1000 }));
+        assert_eq!(machine.program_counter(), 0x200);
+    }
+
+    #[test]
+    pub fn test_keys_of_interest() {
+        let program = vec![
+            0x65, 0x0C, // 0x200: V5 = 0x0C
+            0xE5, 0x9E, // 0x202: skip if key V5 is pressed
+            0xE5, 0xA1, // 0x204: skip if key V5 isn't pressed
+            0xF3, 0x0A, // 0x206: wait for a key
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        assert_eq!(machine.keys_of_interest(), []);
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.keys_of_interest(), [0xC]);
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.keys_of_interest(), [0xC]);
+        machine.step(&[0xC]).unwrap();
+        assert_eq!(machine.keys_of_interest(), (0..=0xF).collect::<Vec<u8>>());
+
+        let mut machine = Machine::new(vec![0x65, 0x10, 0xE5, 0x9E], Quirks::inactive()); // V5 = 0x10, skip if key V5 is pressed
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.keys_of_interest(), [], "Values past 0xF aren't keys");
+    }
+
+    #[test]
+    pub fn test_is_halted() {
+        let program = vec![
+            0x60, 0x01, // V0 = 0x01
+            0xB2, 0x03, // jump to 0x203 + V0
+            0x12, 0x04, // jump to self
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        assert!(!machine.is_halted());
+        machine.tick_many(&[], 2).unwrap();
+        assert!(machine.is_halted(), "A jump landing on itself should halt the machine");
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(machine.program_counter(), 0x204);
+        assert!(machine.is_halted());
+
+        let mut machine = Machine::new(vec![0x60, 0x02, 0xB2, 0x02], Quirks::inactive()); // jump to 0x202 + V0
+        machine.tick(vec![]);
+        assert!(!machine.is_halted());
+        machine.registers.set_value(Register::first(), 0);
+        assert!(machine.is_halted(), "An offset jump landing on itself should halt the machine");
+
+        let mut machine = Machine::new(vec![0x60, 0x01], Quirks::inactive());
+        machine.tick(vec![]);
+        assert!(machine.is_halted(), "Running off the end of the program should halt the machine");
+    }
+
+    #[test]
+    pub fn test_tick_with_keys() {
+        let program = vec![
+            0xE0, 0x9E, // skip if key V0 is pressed
+            0x71, 0x01, // V1 += 0x01
+            0xE0, 0xA1, // skip if key V0 isn't pressed
+            0x72, 0x01, // V2 += 0x01
+            0x70, 0x01, // V0 += 0x01
+            0x12, 0x00, // jump to 0x200
+        ];
+        let mut with_slice = Machine::new(program.clone(), Quirks::inactive());
+        let mut with_mask = Machine::new(program, Quirks::inactive());
+        let keys = [0x1, 0x3, 0xA];
+        for _ in 0..(5 * 18) {
+            with_slice.tick(keys.to_vec());
+            with_mask.tick_with_keys(0b0000_0100_0000_1010).unwrap();
+            assert_eq!(with_slice.program_counter(), with_mask.program_counter());
+        }
+        assert_eq!(with_slice.registers(), with_mask.registers());
+        assert_eq!(with_mask.register(Register::new(0x1)), 15, "Keys other than 1, 3 and A should be released");
+        assert_eq!(with_mask.register(Register::new(0x2)), 3);
+    }
+
+    #[test]
+    pub fn test_wait_for_key_mask() {
+        let mut machine = Machine::new(vec![0xF3, 0x0A], Quirks::inactive()); // V3 = the next key pressed
+        machine.tick_with_keys(0b1010_0000).unwrap();
+        machine.tick_with_keys(0b0010_0000).unwrap();
+        assert_eq!(machine.program_counter(), 0x200, "The key should still be held down");
+        machine.tick_with_keys(0).unwrap();
+        assert_eq!(machine.register(Register::new(0x3)), 0x5);
+        assert_eq!(machine.program_counter(), 0x202);
+    }
+
+    #[test]
+    pub fn test_zero_opcode() {
+        let program = vec![
+            0x60, 0x01, // V0 = 0x01
+        ];
+        let with_zero_opcode = |zero_opcode, is_strict_opcodes| {
+            let config = MachineConfig { zero_opcode, is_strict_opcodes, ..MachineConfig::default() };
+            let mut machine = Machine::with_config(program.clone(), Quirks::inactive(), config);
+            machine.tick(vec![]);
+            machine
+        };
+
+        let mut machine = with_zero_opcode(ZeroOpcode::Halt, true);
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(machine.program_counter(), 0x202);
+        assert!(machine.is_halted());
+
+        let mut machine = with_zero_opcode(ZeroOpcode::Error, false);
+        assert_eq!(machine.try_tick(&[]), Err(MachineError::ZeroOpcode { pc: 0x202 }));
+        assert!(!machine.is_halted());
+
+        let mut machine = with_zero_opcode(ZeroOpcode::Unknown, false);
+        machine.tick_many(&[], 10).unwrap();
+        assert_eq!(machine.program_counter(), 0x216, "Lenient mode should skip over the zeroes");
+
+        let mut machine = with_zero_opcode(ZeroOpcode::Unknown, true);
+        assert_eq!(machine.try_tick(&[]), Err(MachineError::UnknownOpcode { opcode: 0x0000, pc: 0x202 }));
+
+        let config = MachineConfig { zero_opcode: ZeroOpcode::Error, ..MachineConfig::default() };
+        let mut machine = Machine::with_config(vec![0x00, 0xDE], Quirks::inactive(), config);
+        machine.tick(vec![]);
+        assert!(machine.is_halted(), "0x00DE should end the program regardless of the setting");
+    }
+
+    #[test]
+    pub fn test_save_load_flags() {
+        let program = vec![
+            0x60, 0x11, // V0 = 0x11
+            0x61, 0x22, // V1 = 0x22
+            0x62, 0x33, // V2 = 0x33
+            0xF2, 0x75, // save V0..=V2 to the flags
+            0x60, 0x00, // V0 = 0x00
+            0x61, 0x00, // V1 = 0x00
+            0x62, 0x00, // V2 = 0x00
+            0xF1, 0x85, // load V0..=V1 from the flags
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.tick_many(&[], 8).unwrap();
+        assert_eq!(machine.flags(), [0x11, 0x22, 0x33, 0, 0, 0, 0, 0]);
+        assert_eq!(machine.registers()[..3], [0x11, 0x22, 0x00]);
+
+        machine.reset();
+        assert_eq!(machine.flags()[..3], [0x11, 0x22, 0x33], "Flags should survive a reset");
+
+        let mut machine = Machine::new(vec![0xFF, 0x85], Quirks::inactive()); // load V0..=VF from the flags
+        machine.set_flags([1, 2, 3, 4, 5, 6, 7, 8]);
+        machine.registers.set_value(Register::flag(), 0xAA);
+        machine.tick(vec![]);
+        assert_eq!(machine.registers()[..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(machine.register(Register::flag()), 0xAA, "Registers without a flag should be untouched");
+    }
+
+    #[test]
+    pub fn test_big_digit_address() {
+        let program = vec![
+            0x61, 0x0B, // V1 = 0x0B
+            0xF1, 0x30, // I = address of the big sprite for V1
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.index(), heap::OFFSET_BIG_FONT + 0xB * 10);
+        assert_eq!(machine.read_memory(machine.index(), 10), Ok(&[0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC][..]));
+    }
+
+    #[test]
+    pub fn test_set_font() {
+        let program = vec![
+            0x60, 0x0A, // V0 = 0x0A
+            0xF0, 0x29, // I = address of the sprite for V0
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        let font: Vec<u8> = (0..80).map(|i| 0x80 | i).collect();
+        machine.set_font(&font).unwrap();
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.read_memory(machine.index(), 5), Ok(&font[50..55]));
+
+        assert_eq!(machine.set_font(&[0xF0; 160]), Err(MachineError::InvalidFontLength { len: 160 }));
+    }
+
+    #[test]
+    pub fn test_try_tick_errors() {
+        let strict = MachineConfig { is_strict_opcodes: true, ..MachineConfig::default() };
+        let mut machine = Machine::with_config(vec![0x5A, 0xB1], Quirks::inactive(), strict);
+        assert_eq!(machine.try_tick(&[]), Err(MachineError::UnknownOpcode { opcode: 0x5AB1, pc: 0x200 }));
+
+        let mut machine = Machine::new(vec![0x22, 0x02, 0x22, 0x00], Quirks::inactive()); // call each other
+        for _ in 0..16 {
+            machine.try_tick(&[]).unwrap();
+        }
+        assert_eq!(machine.try_tick(&[]), Err(MachineError::StackOverflow));
+
+        let mut machine = Machine::new(vec![0x00, 0xEE], Quirks::inactive()); // return
+        assert_eq!(machine.try_tick(&[]), Err(MachineError::StackUnderflow));
+        assert_eq!(machine.program_counter(), 0x200, "The program counter should stay on the failed instruction");
+
+
+        // Storing as decimal, dumping and loading registers all touch three bytes, which run past the end.
+        for opcode in [0xF0, 0x33, 0xF2, 0x55, 0xF2, 0x65].chunks(2) {
+            let mut machine = Machine::new([0xAF, 0xFE, opcode[0], opcode[1]], Quirks::inactive()); // I = 0xFFE
+            machine.try_tick(&[]).unwrap();
+            assert_eq!(machine.try_tick(&[]), Err(MachineError::MemoryOutOfBounds { address: 0x1000 }));
+        }
+    }
+
+    #[test]
+    pub fn test_custom_resolution() {
+        let program = vec![
+            0x60, 0x3C, // V0 = 0x3C
+            0x61, 0x2C, // V1 = 0x2C
+            0xF2, 0x29, // I = address of digit V2
+            0xD0, 0x15, // draw(V0, V1, 5)
+        ];
+        let config = MachineConfig { resolution: (64, 48), ..MachineConfig::default() };
+        let mut machine = Machine::with_config(program, Quirks::inactive(), config);
+        assert_eq!(machine.resolution(), (64, 48));
+        machine.tick_many(&[], 4).unwrap();
+        assert_eq!(machine.to_rgba().len(), 64 * 48 * 4);
+        assert_eq!(machine.to_ascii().lines().count(), 48);
+        assert_eq!(&machine.to_ascii().lines().nth(44).unwrap()[60..], "####", "The sprite should be drawn below row 32");
+    }
+
+    #[test]
+    pub fn test_load_address() {
+        let program = vec![
+            0x60, 0x42, // V0 = 0x42
+            0x00, 0xE0, // clear screen
+        ];
+        let config = MachineConfig { load_address: 0x600, ..MachineConfig::default() };
+        let mut machine = Machine::with_config(program, Quirks::inactive(), config);
+        assert_eq!(machine.program_counter(), 0x600);
+        assert_eq!(machine.read_memory(0x200, 2), Ok(&[0, 0][..]));
+
+        let executed = machine.step(&[]).unwrap();
+        assert_eq!(executed.pc_before, 0x600);
+        assert_eq!(machine.register(Register::new(0x0)), 0x42);
+
+        machine.reset();
+        assert_eq!(machine.program_counter(), 0x600, "Resetting should restart from the load address");
+
+        let config = MachineConfig { load_address: 0x1000, ..MachineConfig::default() };
+        let error = Machine::try_with_config([], Quirks::inactive(), config).err();
+        assert_eq!(error, Some(MachineError::InvalidLoadAddress { address: 0x1000 }));
+    }
+
+    #[test]
+    pub fn test_stack_capacity() {
+        let program = vec![
+            0x70, 0x01, // V0 += 0x01
+            0x22, 0x00, // call 0x200, recursing forever
+        ];
+        let mut machine = Machine::new(program.clone(), Quirks::inactive());
+        assert_eq!(machine.tick_many(&[], 34), Err(MachineError::StackOverflow));
+        assert_eq!(machine.stack_depth(), 16);
+
+        let config = MachineConfig { stack_capacity: 64, ..MachineConfig::default() };
+        let mut machine = Machine::with_config(program, Quirks::inactive(), config);
+        assert_eq!(machine.tick_many(&[], 128), Ok(128), "Calls beyond 16 deep should fit in a larger stack");
+        assert_eq!(machine.stack_depth(), 64);
+        machine.step(&[]).unwrap();
+        assert_eq!(machine.step(&[]).err(), Some(MachineError::StackOverflow));
+
+        machine.reset();
+        machine.tick_many(&[], 40).unwrap();
+        assert_eq!(machine.stack_depth(), 20, "Resetting should keep the capacity");
+    }
+
+    #[test]
+    pub fn test_program_counter_wraps() {
+        let mut machine = Machine::new(vec![0x1F, 0xFE], Quirks::inactive()); // jump to 0xFFE
+        machine.write_memory(0xFFE, &[0x60, 0x42]).unwrap(); // V0 = 0x42
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.register(Register::new(0x0)), 0x42);
+        assert_eq!(machine.program_counter(), 0x000);
+
+        // An instruction split across the end of memory is read from both ends.
+        let mut machine = Machine::new(vec![0x1F, 0xFF], Quirks::inactive()); // jump to 0xFFF
+        machine.write_memory(0xFFF, &[0x61]).unwrap();
+        machine.write_memory(0x000, &[0x07]).unwrap(); // V1 = 0x07
+        assert_eq!(machine.tick_many(&[], 2), Ok(2));
+        assert_eq!(machine.register(Register::new(0x1)), 0x07);
+        assert_eq!(machine.program_counter(), 0x001);
+
+        // Offset jumps past the end wrap as well.
+        let mut machine = Machine::new(vec![0x60, 0x10, 0xBF, 0xF8], Quirks::inactive()); // V0 = 0x10, jump to 0xFF8 + V0
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.program_counter(), 0x008);
+    }
+
+    #[test]
+    pub fn test_self_modifying_code() {
+        let program = vec![
+            0x60, 0x62, // 0x200: V0 = 0x62
+            0x61, 0x09, // 0x202: V1 = 0x09
+            0xA2, 0x08, // 0x204: I = 0x208
+            0xF1, 0x55, // 0x206: store V0..V1 at 0x208, which writes V2 = 0x09 over the end of the program
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        while !machine.is_halted() {
+            machine.step(&[]).unwrap();
+        }
+        assert_eq!(machine.register(Register::new(0x2)), 0x09, "The instruction written by the program should run");
+        assert_eq!(machine.program_counter(), 0x20A);
+    }
+
+    #[test]
+    pub fn test_dump_load_round_trip() {
+        let mut program = Vec::new();
+        for i in 0..16u8 {
+            program.extend([0x60 | i, 0xA0 + i]); // VI = 0xA0 + I
+        }
+        program.extend([
+            0xA3, 0x00, // I = 0x300
+            0xFF, 0x55, // dump V0..=VF at I
+            0x6F, 0x00, // VF = 0x00
+            0x60, 0x00, // V0 = 0x00
+            0xA3, 0x00, // I = 0x300
+            0xFF, 0x65, // load V0..=VF from I
+        ]);
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.heap.set_byte(0x310, 0xEE).unwrap();
+        machine.tick_many(&[], 16 + 6).unwrap();
+
+        let expected: Vec<u8> = (0..16).map(|i| 0xA0 + i).collect();
+        assert_eq!(machine.heap.get_bytes(0x300, 16), &expected[..]);
+        assert_eq!(machine.heap.get_bytes(0x310, 1), &[0xEE], "Dump should write exactly 16 bytes");
+        assert_eq!(machine.registers.dump(Register::flag()), &expected[..]);
+        assert_eq!(machine.index(), 0x310);
+    }
+
+    #[test]
+    pub fn test_wait_for_key_consumes_held_keys() {
+        let program = vec![
+            0x62, 0x07, // V2 = 0x07
+            0xF1, 0x0A, // V1 = wait for key
+            0xE2, 0x9E, // skip if key V2 is down
+            0x73, 0x01, // V3 += 0x01
+            0x12, 0x04, // jump to 0x204
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.step(&[0x3, 0x7]).unwrap();
+        machine.step(&[0x3, 0x7]).unwrap();
+        machine.step(&[0x7]).unwrap();
+        assert_eq!(machine.register(Register::new(0x1)), 0x3);
+
+        for _ in 0..3 {
+            machine.step(&[0x7]).unwrap();
+        }
+        assert_eq!(machine.register(Register::new(0x3)), 1, "A key held through the wait shouldn't count as pressed");
+
+        for _ in 0..3 {
+            machine.step(&[]).unwrap();
+        }
+        assert_eq!(machine.register(Register::new(0x3)), 2);
+        machine.step(&[0x7]).unwrap();
+        assert_eq!(machine.program_counter(), 0x208, "The key should count once it's pressed again");
+    }
+
+    #[test]
+    pub fn test_wait_for_key_release() {
+        let program = vec![
+            0x70, 0x01, // V0 += 0x01
+            0xF1, 0x0A, // V1 = wait for key
+            0x12, 0x00, // jump to 0x200
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.program_counter(), 0x202);
+        for _ in 0..5 {
+            machine.tick_many(&[0x7, 0x3], 10).unwrap();
+            assert_eq!(machine.program_counter(), 0x202, "Key should be held until released");
+        }
+        assert_eq!(machine.register(Register::new(0x1)), 0);
+
+        machine.tick_many(&[0x7], 4).unwrap();
+        assert_eq!(machine.register(Register::new(0x1)), 0x3, "The lowest key held down should be the one waited on");
+        assert_eq!(machine.register(Register::first()), 0x02);
+        assert_eq!(machine.program_counter(), 0x202, "Next wait should not complete while a key is held");
+
+        machine.tick_many(&[0x7], 10).unwrap();
+        assert_eq!(machine.register(Register::first()), 0x02);
+    }
+
+    #[test]
+    pub fn test_large_memory() {
+        let mut program = vec![0xFF; 0x1200];
+        program[..4].copy_from_slice(&[
+            0x60, 0x42, // V0 = 0x42
+            0xF0, 0x55, // dump V0 at I
+        ]);
+        let config = MachineConfig { memory_size: 0x10000, ..MachineConfig::default() };
+        let mut machine = Machine::with_config(program, Quirks::inactive(), config);
+        assert_eq!(machine.heap.get_bytes(0x200 + 0x11FF, 1), &[0xFF]);
+
+        machine.registers.index = 0xFFFF;
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.heap.get_bytes(0xFFFF, 1), &[0x42]);
+        assert_eq!(machine.index(), 0x10000);
+    }
+
+    #[test]
+    pub fn test_write_read_memory() {
+        let mut machine = Machine::new(vec![0xA3, 0x00], Quirks::inactive());
+        machine.write_memory(0x300, &[0xDE, 0xAD, 0xBE, 0xEF]).unwrap();
+        assert_eq!(machine.read_memory(0x300, 4), Ok(&[0xDE, 0xAD, 0xBE, 0xEF][..]));
+        assert_eq!(machine.read_memory(0x200, 2), Ok(&[0xA3, 0x00][..]));
+
+        assert_eq!(machine.write_memory(0xFFE, &[1, 2, 3]), Err(MachineError::MemoryOutOfBounds { address: 0x1000 }));
+        assert_eq!(machine.read_memory(0xFFE, 2), Ok(&[0, 0][..]), "Failed writes should leave memory untouched");
+        assert_eq!(machine.read_memory(0x1001, 1), Err(MachineError::MemoryOutOfBounds { address: 0x1001 }));
+        assert_eq!(
+            machine.read_memory(usize::MAX, 2),
+            Err(MachineError::MemoryOutOfBounds { address: usize::MAX }),
+            "Ranges past the end of the address space should fail rather than overflow"
+        );
+    }
+
+    #[test]
+    pub fn test_new_empty() {
+        let mut machine = Machine::new_empty(Quirks::inactive());
+        assert_eq!(machine.read_memory(0x200, 4), Ok(&[0, 0, 0, 0][..]));
+        assert_eq!(machine.read_memory(heap::OFFSET_FONT, 5), Ok(&[0xF0, 0x90, 0x90, 0x90, 0xF0][..]));
+
+        machine.write_memory(0x200, &[
+            0x60, 0x12, // V0 = 0x12
+            0x70, 0x30, // V0 += 0x30
+        ]).unwrap();
+        machine.tick_many(&[], 2).unwrap();
+        assert_eq!(machine.register(Register::new(0x0)), 0x42);
+        assert_eq!(machine.program_counter(), 0x204);
+    }
+
+    #[test]
+    pub fn test_memory_dump() {
+        let mut program = b"Hi!".to_vec();
+        program.extend((0..16).map(|i| i * 0x10));
+        let machine = Machine::new(&program, Quirks::inactive());
+        assert_eq!(machine.memory_dump(0x200, program.len()), Ok(&program[..]));
+        assert_eq!(machine.memory_dump(0x200, 0), Ok(&[][..]));
+        assert_eq!(machine.memory_dump(0xFFF, 2), Err(MachineError::MemoryOutOfBounds { address: 0x1000 }));
+    }
+
+    #[test]
+    pub fn test_dump_index() {
+        let program = vec![
+            0xA3, 0x00, // I = 0x300
+            0xF2, 0x55, // dump V0..V2 at I
+            0xF1, 0x65, // load V0..V1 from I
+        ];
+        let modes = [
+            (DumpIndex::IncrementByXPlus1, 0x303, 0x305),
+            (DumpIndex::IncrementByX, 0x302, 0x303),
+            (DumpIndex::Unchanged, 0x300, 0x300),
+        ];
+        for (dump_index, after_dump, after_load) in modes {
+            let quirks = Quirks { dump_index, ..Quirks::inactive() };
+            let mut machine = Machine::new(program.clone(), quirks);
+            machine.tick_many(&[], 2).unwrap();
+            assert_eq!(machine.index(), after_dump, "{:?}", dump_index);
+            machine.tick_many(&[], 1).unwrap();
+            assert_eq!(machine.index(), after_load, "{:?}", dump_index);
+        }
+    }
+
+    #[test]
+    pub fn test_i_overflow() {
+        let program = vec![
+            0xAF, 0xFE, // I = 0xFFE
+            0x60, 0x05, // V0 = 0x05
+            0x6F, 0x07, // VF = 0x07
+            0xF0, 0x1E, // I += V0
+            0xF0, 0x1E, // I += V0
+        ];
+        let mut machine = Machine::new(program.clone(), Quirks::inactive());
+        machine.tick_many(&[], 4).unwrap();
+        assert_eq!(machine.index(), 0x003, "I should wrap around within memory");
+        assert_eq!(machine.register(Register::flag()), 0x07, "VF should be untouched without the quirk");
+
+        let quirks = Quirks { is_i_overflow_sets_vf: true, ..Quirks::inactive() };
+        let mut machine = Machine::new(program, quirks);
+        machine.tick_many(&[], 4).unwrap();
+        assert_eq!(machine.index(), 0x003);
+        assert_eq!(machine.register(Register::flag()), 1);
+        machine.tick_many(&[], 1).unwrap();
+        assert_eq!(machine.index(), 0x008);
+        assert_eq!(machine.register(Register::flag()), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    pub fn test_from_reader() {
+        let program: &[u8] = &[0x60, 0x42];
+        let mut machine = Machine::from_reader(program, Quirks::inactive()).unwrap();
+        machine.tick_many(&[], 1).unwrap();
+        assert_eq!(machine.register(Register::first()), 0x42);
+
+        let program = vec![0x00; 0x1000 - 0x200];
+        assert!(Machine::from_reader(program.as_slice(), Quirks::inactive()).is_ok());
+
+        let program = vec![0x00; 4000];
+        let error = Machine::from_reader(program.as_slice(), Quirks::inactive()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    pub fn test_program_too_large() {
+        let error = Machine::try_new(vec![0x00; 4000], Quirks::inactive()).err();
+        assert_eq!(error, Some(MachineError::ProgramTooLarge { overflow: 4000 - (0x1000 - 0x200) }));
+
+        let config = MachineConfig { memory_size: 0x2000, ..MachineConfig::default() };
+        assert!(Machine::try_with_config(vec![0x00; 4000], Quirks::inactive(), config).is_ok());
+    }
+
+    #[test]
+    pub fn test_snapshot_restore() {
+        let program = vec![
+            0x60, 0x05, // V0 = 0x05
+            0xF0, 0x15, // delay = V0
+            0x22, 0x08, // call 0x208
+            0x12, 0x06, // jump to self
+            0xC1, 0xFF, // V1 = rand() & 0xFF
+            0xA2, 0x00, // I = 0x200
+            0xD0, 0x15, // draw(V0, V1, 5)
+            0xF1, 0x55, // dump V0..=V1 at I
+            0x00, 0xEE, // return
+        ];
+        let mut machine = Machine::with_seed(program, Quirks::active(), 7);
+        for _ in 0..3 {
+            machine.tick_many(&[], 1).unwrap();
+        }
+        let snapshot = machine.snapshot();
+
+        for _ in 0..6 {
+            machine.tick_many(&[], 1).unwrap();
+        }
+        let expected_final = machine.snapshot();
+        assert_ne!(expected_final, snapshot);
+
+        machine.restore(&snapshot).unwrap();
+        assert_eq!(machine.snapshot(), snapshot);
+
+        for _ in 0..6 {
+            machine.tick_many(&[], 1).unwrap();
+        }
+        assert_eq!(machine.snapshot(), expected_final, "Restored machine should replay identically");
+    }
+
+    #[test]
+    pub fn test_restore_invalid_snapshot() {
+        let program = vec![
+            0x22, 0x04, // call 0x204
+            0x12, 0x00, // jump to 0x200
+            0x60, 0x05, // V0 = 0x05
+            0xD0, 0x05, // draw(V0, V0, 5)
+            0x12, 0x08, // jump to self
+        ];
+        let mut machine = Machine::new(program, Quirks::inactive());
+        machine.tick_many(&[], 4).unwrap();
+        let before = machine.snapshot();
+
+        let bad_pixels = MachineSnapshot { pixels: vec![0; 3], ..before.clone() };
+        assert_eq!(machine.restore(&bad_pixels), Err(MachineError::InvalidDisplayData { len: 3 }));
+        assert_eq!(machine.snapshot(), before, "A snapshot with bad pixels should leave the machine unchanged");
+
+        let high_res = MachineSnapshot { is_high_res: true, ..before.clone() };
+        assert!(machine.restore(&high_res).is_err(), "Low resolution pixels shouldn't restore in high resolution");
+        assert_eq!(machine.snapshot(), before);
+
+        let deep_stack = MachineSnapshot { stack: vec![0x202; machine.config.stack_capacity + 1], ..before.clone() };
+        assert_eq!(machine.restore(&deep_stack), Err(MachineError::StackOverflow));
+        assert_eq!(machine.snapshot(), before, "A snapshot with too many stack frames should leave the machine unchanged");
     }
 }
@@ -1,10 +1,59 @@
 
+/// How `FX55` and `FX65` change `I` after dumping or loading registers `V0` to `VX`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DumpIndex {
+    /// `I` is left pointing just past the last byte accessed, as on the COSMAC VIP
+    IncrementByXPlus1,
+    /// `I` is left pointing at the last byte accessed, as on CHIP-48
+    IncrementByX,
+    /// `I` is left unchanged, as on SUPER-CHIP
+    Unchanged,
+}
+
+/// Behaviours which differ between CHIP-8 interpreters.
+///
+/// The platform presets set these flags as follows:
+///
+/// | Flag                    | `cosmac_vip`        | `chip48`       | `superchip` | `xochip`            |
+/// |-------------------------|---------------------|----------------|-------------|---------------------|
+/// | `is_lazy_shift`         | false               | true           | true        | false               |
+/// | `dump_index`            | `IncrementByXPlus1` | `IncrementByX` | `Unchanged` | `IncrementByXPlus1` |
+/// | `is_wrap_x`             | false               | false          | false       | true                |
+/// | `is_wrap_y`             | false               | false          | false       | true                |
+/// | `is_reset_vf_on_logic`  | true                | false          | false       | false               |
+/// | `is_jump_vx`            | false               | true           | true        | false               |
+/// | `is_display_wait`       | true                | false          | false       | false               |
+/// | `is_i_overflow_sets_vf` | false               | false          | false       | false               |
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quirks {
+    /// The shift instructions shift `VX` in place, ignoring `VY`
     pub is_lazy_shift: bool,
-    pub is_static_dump_index: bool,
+    /// How dumping and loading registers up to `VX` changes `I`
+    pub dump_index: DumpIndex,
+    /// Sprites drawn past the right edge of the screen wrap around to the left edge, instead of being clipped
+    pub is_wrap_x: bool,
+    /// Sprites drawn past the bottom edge of the screen wrap around to the top edge, instead of being clipped
+    pub is_wrap_y: bool,
+    /// The logical OR, AND, and XOR instructions reset `VF` to 0, as on the COSMAC VIP
+    pub is_reset_vf_on_logic: bool,
+    /// `BXNN` jumps to `XNN + VX` as on SUPER-CHIP, instead of `BNNN` jumping to `NNN + V0`
+    pub is_jump_vx: bool,
+    /// Drawing a sprite waits for the vertical blank, as on the COSMAC VIP, which limits drawing to
+    /// one sprite per 60Hz frame. This only has an effect when several instructions are executed per
+    /// frame with `Machine::tick_many`, where no further instructions run in a frame after a draw.
+    pub is_display_wait: bool,
+    /// `FX1E` sets `VF` to 1 when `I` crosses `0x1000`, and to 0 otherwise, as on the Amiga interpreter
+    pub is_i_overflow_sets_vf: bool,
 }
 
 impl Quirks {
+    /// Starts building a custom set of quirks from `inactive`, for flags which don't match any preset.
+    pub fn builder() -> QuirksBuilder {
+        QuirksBuilder::new()
+    }
+
     pub fn from_flag(is_active: bool) -> Self {
         if is_active {
             Quirks::active()
@@ -16,14 +65,185 @@ impl Quirks {
     pub fn active() -> Self {
         Quirks {
             is_lazy_shift: true,
-            is_static_dump_index: true,
+            dump_index: DumpIndex::Unchanged,
+            is_wrap_x: false,
+            is_wrap_y: false,
+            is_reset_vf_on_logic: false,
+            is_jump_vx: false,
+            is_display_wait: false,
+            is_i_overflow_sets_vf: false,
         }
     }
 
     pub fn inactive() -> Self {
         Quirks {
             is_lazy_shift: false,
-            is_static_dump_index: false,
+            dump_index: DumpIndex::IncrementByXPlus1,
+            is_wrap_x: false,
+            is_wrap_y: false,
+            is_reset_vf_on_logic: false,
+            is_jump_vx: false,
+            is_display_wait: false,
+            is_i_overflow_sets_vf: false,
+        }
+    }
+
+    /// The original interpreter for the COSMAC VIP
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            is_lazy_shift: false,
+            dump_index: DumpIndex::IncrementByXPlus1,
+            is_wrap_x: false,
+            is_wrap_y: false,
+            is_reset_vf_on_logic: true,
+            is_jump_vx: false,
+            is_display_wait: true,
+            is_i_overflow_sets_vf: false,
+        }
+    }
+
+    /// CHIP-48 for the HP-48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            is_lazy_shift: true,
+            dump_index: DumpIndex::IncrementByX,
+            is_wrap_x: false,
+            is_wrap_y: false,
+            is_reset_vf_on_logic: false,
+            is_jump_vx: true,
+            is_display_wait: false,
+            is_i_overflow_sets_vf: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1, the successor to CHIP-48
+    pub fn superchip() -> Self {
+        Quirks {
+            is_lazy_shift: true,
+            dump_index: DumpIndex::Unchanged,
+            is_wrap_x: false,
+            is_wrap_y: false,
+            is_reset_vf_on_logic: false,
+            is_jump_vx: true,
+            is_display_wait: false,
+            is_i_overflow_sets_vf: false,
+        }
+    }
+
+    /// XO-CHIP, as implemented by Octo
+    pub fn xochip() -> Self {
+        Quirks {
+            is_lazy_shift: false,
+            dump_index: DumpIndex::IncrementByXPlus1,
+            is_wrap_x: true,
+            is_wrap_y: true,
+            is_reset_vf_on_logic: false,
+            is_jump_vx: false,
+            is_display_wait: false,
+            is_i_overflow_sets_vf: false,
         }
     }
 }
+
+/// Builds a `Quirks` one flag at a time, starting from `Quirks::inactive`,
+/// e.g. `Quirks::builder().lazy_shift(true).wrap_sprites(true).build()`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QuirksBuilder {
+    quirks: Quirks,
+}
+
+impl QuirksBuilder {
+    pub fn new() -> Self {
+        QuirksBuilder { quirks: Quirks::inactive() }
+    }
+
+    pub fn lazy_shift(mut self, is_lazy_shift: bool) -> Self {
+        self.quirks.is_lazy_shift = is_lazy_shift;
+        self
+    }
+
+    pub fn dump_index(mut self, dump_index: DumpIndex) -> Self {
+        self.quirks.dump_index = dump_index;
+        self
+    }
+
+    /// Sets both `wrap_x` and `wrap_y`, for platforms which wrap sprites around every edge or none.
+    pub fn wrap_sprites(mut self, is_wrap_sprites: bool) -> Self {
+        self.quirks.is_wrap_x = is_wrap_sprites;
+        self.quirks.is_wrap_y = is_wrap_sprites;
+        self
+    }
+
+    pub fn wrap_x(mut self, is_wrap_x: bool) -> Self {
+        self.quirks.is_wrap_x = is_wrap_x;
+        self
+    }
+
+    pub fn wrap_y(mut self, is_wrap_y: bool) -> Self {
+        self.quirks.is_wrap_y = is_wrap_y;
+        self
+    }
+
+    pub fn reset_vf_on_logic(mut self, is_reset_vf_on_logic: bool) -> Self {
+        self.quirks.is_reset_vf_on_logic = is_reset_vf_on_logic;
+        self
+    }
+
+    pub fn jump_vx(mut self, is_jump_vx: bool) -> Self {
+        self.quirks.is_jump_vx = is_jump_vx;
+        self
+    }
+
+    pub fn display_wait(mut self, is_display_wait: bool) -> Self {
+        self.quirks.is_display_wait = is_display_wait;
+        self
+    }
+
+    pub fn i_overflow_sets_vf(mut self, is_i_overflow_sets_vf: bool) -> Self {
+        self.quirks.is_i_overflow_sets_vf = is_i_overflow_sets_vf;
+        self
+    }
+
+    pub fn build(self) -> Quirks {
+        self.quirks
+    }
+}
+
+impl Default for QuirksBuilder {
+    fn default() -> Self {
+        QuirksBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_builder() {
+        assert_eq!(Quirks::builder().build(), Quirks::inactive());
+
+        let quirks = Quirks::builder()
+            .lazy_shift(true)
+            .dump_index(DumpIndex::IncrementByX)
+            .wrap_sprites(true)
+            .reset_vf_on_logic(true)
+            .jump_vx(true)
+            .display_wait(true)
+            .i_overflow_sets_vf(true)
+            .build();
+        assert!(quirks.is_lazy_shift);
+        assert_eq!(quirks.dump_index, DumpIndex::IncrementByX);
+        assert!(quirks.is_wrap_x && quirks.is_wrap_y);
+        assert!(quirks.is_reset_vf_on_logic);
+        assert!(quirks.is_jump_vx);
+        assert!(quirks.is_display_wait);
+        assert!(quirks.is_i_overflow_sets_vf);
+
+        let quirks = Quirks::builder().lazy_shift(true).dump_index(DumpIndex::Unchanged).build();
+        assert_eq!(quirks, Quirks::active());
+
+        let quirks = Quirks::builder().wrap_x(true).build();
+        assert!(quirks.is_wrap_x && !quirks.is_wrap_y);
+    }
+}
@@ -1,9 +1,9 @@
-use crate::heap;
+use core::fmt;
 
 const GENERAL_REGISTER_COUNT: usize = 16;
 const FLAG_REGISTER_IDX: u8 = 0xF;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct Register(usize);
 
 impl Register {
@@ -20,11 +20,30 @@ impl Register {
         Register(0)
     }
 
+    /// Every general register in order, from V0 to VF.
+    pub fn all() -> impl Iterator<Item = Register> {
+        (0..GENERAL_REGISTER_COUNT).map(Register)
+    }
+
     pub fn idx(self) -> usize {
         self.0
     }
 }
 
+/// Registers are written by their assembly names, from `V0` to `VF`.
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "V{:X}", self.0)
+    }
+}
+
+/// The same as `Display`, so instructions in logs and failed assertions read like assembly.
+impl fmt::Debug for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
 pub struct Registers {
     general: [u8; GENERAL_REGISTER_COUNT],
     pub index: usize,
@@ -32,14 +51,19 @@ pub struct Registers {
 }
 
 impl Registers {
-    pub fn new() -> Registers {
+    pub fn new(program_counter: usize) -> Registers {
         Registers {
             general: [0; GENERAL_REGISTER_COUNT],
             index: 0,
-            program_counter: heap::OFFSET_DATA,
+            program_counter,
         }
     }
 
+    /// A copy of the general registers, indexed by register number.
+    pub fn snapshot(&self) -> [u8; GENERAL_REGISTER_COUNT] {
+        self.general
+    }
+
     pub fn get_value(&self, register: Register) -> u8 {
         self.general[register.idx()]
     }
@@ -79,22 +103,22 @@ impl Registers {
     pub fn add_registers(&mut self, to: Register, from: Register) {
         let to_val = self.get_value(to);
         let from_val = self.get_value(from);
-        self.set_flag((to_val as u16) + (from_val as u16) > 255);
         self.set_value(to, to_val.wrapping_add(from_val));
+        self.set_flag((to_val as u16) + (from_val as u16) > 255);
     }
 
     pub fn sub_registers(&mut self, to: Register, from: Register) {
         let to_val = self.get_value(to);
         let from_val = self.get_value(from);
-        self.set_flag((to_val as i16) - (from_val as i16) >= 0);
         self.set_value(to, to_val.wrapping_sub(from_val));
+        self.set_flag((to_val as i16) - (from_val as i16) >= 0);
     }
 
     pub fn sub_registers_reversed(&mut self, to: Register, from: Register) {
         let to_val = self.get_value(to);
         let from_val = self.get_value(from);
-        self.set_flag((from_val as i16) - (to_val as i16) >= 0);
         self.set_value(to, from_val.wrapping_sub(to_val));
+        self.set_flag((from_val as i16) - (to_val as i16) >= 0);
     }
 
     pub fn shr_registers(&mut self, to: Register, from: Register, is_lazy_shift: bool) {
@@ -103,8 +127,8 @@ impl Registers {
         } else {
             self.get_value(to) // VX = VX >> 1
         };
-        self.set_flag((from_val & 1) != 0);
         self.set_value(to, from_val >> 1);
+        self.set_flag((from_val & 1) != 0);
     }
 
     pub fn shl_registers(&mut self, to: Register, from: Register, is_lazy_shift: bool) {
@@ -113,10 +137,11 @@ impl Registers {
         } else {
             self.get_value(to) // VX = VX << 1
         };
-        self.set_flag((from_val & 0b1000_0000) != 0);
         self.set_value(to, from_val << 1);
+        self.set_flag((from_val & 0b1000_0000) != 0);
     }
 
+    /// Sets `VF` to 1 or 0. Arithmetic sets the flag after its result, so the flag wins when `VF` is the target.
     pub fn set_flag(&mut self, enable: bool) {
         self.set_value(Register::flag(), enable as u8);
     }
@@ -125,9 +150,82 @@ impl Registers {
         &self.general[0..=max_register.idx()]
     }
 
+    /// Fills the registers from `V0` upwards. Any bytes past `VF` have no register to go into and are ignored.
     pub fn load(&mut self, bytes: &[u8]) {
-        for i in 0..bytes.len() {
-            self.general[i] = bytes[i];
+        let len = bytes.len().min(GENERAL_REGISTER_COUNT);
+        self.general[..len].copy_from_slice(&bytes[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap;
+
+    #[test]
+    pub fn test_all_registers() {
+        let mut registers = Registers::new(heap::OFFSET_DATA);
+        for register in Register::all() {
+            registers.set_value(register, register.idx() as u8 * 2);
         }
+
+        assert_eq!(Register::all().count(), 16);
+        assert_eq!(Register::all().last(), Some(Register::flag()));
+        for register in Register::all() {
+            assert_eq!(registers.get_value(register), register.idx() as u8 * 2);
+        }
+        assert_eq!(registers.snapshot(), core::array::from_fn(|i| i as u8 * 2));
+    }
+
+    #[test]
+    pub fn test_register_names() {
+        assert_eq!(format!("{}", Register::new(0xA)), "VA");
+        assert_eq!(format!("{}", Register::first()), "V0");
+        assert_eq!(format!("{:?}", Register::flag()), "VF");
+    }
+
+    #[test]
+    pub fn test_flag_as_operand() {
+        let (flag, other) = (Register::flag(), Register::new(0x1));
+        let mut registers = Registers::new(heap::OFFSET_DATA);
+        let mut run = |vf: u8, v1: u8, op: fn(&mut Registers, Register, Register)| {
+            registers.set_value(flag, vf);
+            registers.set_value(other, v1);
+            op(&mut registers, flag, other);
+            registers.get_value(flag)
+        };
+
+        // With VF as the target, the flag should win over the result.
+        assert_eq!(run(0xFF, 0x02, Registers::add_registers), 1);
+        assert_eq!(run(0x10, 0x02, Registers::add_registers), 0);
+        assert_eq!(run(0x05, 0x02, Registers::sub_registers), 1);
+        assert_eq!(run(0x01, 0x02, Registers::sub_registers), 0);
+        assert_eq!(run(0x01, 0x02, Registers::sub_registers_reversed), 1);
+        assert_eq!(run(0x05, 0x02, Registers::sub_registers_reversed), 0);
+        assert_eq!(run(0x00, 0x81, |r, to, from| r.shr_registers(to, from, false)), 1);
+        assert_eq!(run(0x81, 0x00, |r, to, from| r.shr_registers(to, from, true)), 1);
+        assert_eq!(run(0x00, 0x40, |r, to, from| r.shl_registers(to, from, false)), 0);
+        assert_eq!(run(0x80, 0x00, |r, to, from| r.shl_registers(to, from, true)), 1);
+
+        // With VF as the source, its value should be read before the flag replaces it.
+        registers.set_value(other, 0xFF);
+        registers.set_value(flag, 0x02);
+        registers.add_registers(other, flag);
+        assert_eq!((registers.get_value(other), registers.get_value(flag)), (0x01, 1));
+        registers.set_value(other, 0x01);
+        registers.set_value(flag, 0x02);
+        registers.sub_registers(other, flag);
+        assert_eq!((registers.get_value(other), registers.get_value(flag)), (0xFF, 0));
+    }
+
+    #[test]
+    pub fn test_load_too_many() {
+        let mut registers = Registers::new(heap::OFFSET_DATA);
+        let bytes: Vec<u8> = (1..=17).collect();
+        registers.load(&bytes);
+        assert_eq!(registers.snapshot(), core::array::from_fn(|i| i as u8 + 1));
+
+        registers.load(&[0xAA, 0xBB]);
+        assert_eq!(registers.snapshot()[..3], [0xAA, 0xBB, 3], "Only the given registers should be replaced");
     }
 }
@@ -0,0 +1,152 @@
+use core::fmt;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// An error found while parsing an input log with `InputLog::parse`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputLogError {
+    /// The log doesn't start with a `seed` line
+    MissingSeed,
+
+    /// The line isn't a frame number followed by a hex key mask
+    InvalidEntry { line: usize },
+
+    /// The line's frame number isn't after the one before it
+    OutOfOrder { line: usize },
+}
+
+impl fmt::Display for InputLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputLogError::MissingSeed => write!(f, "Input log doesn't start with a seed"),
+            InputLogError::InvalidEntry { line } => write!(f, "Line {}: expected a frame number and a hex key mask", line),
+            InputLogError::OutOfOrder { line } => write!(f, "Line {}: frame is out of order", line),
+        }
+    }
+}
+
+impl core::error::Error for InputLogError {}
+
+/// The keys held down during each frame of a session, for replaying it exactly.
+///
+/// A machine created with `Machine::with_seed(program, quirks, log.seed())` and ticked with `keys_at(frame)`
+/// for each frame behaves exactly as the machine which was recorded, as long as it runs the same program
+/// with the same quirks and instructions per frame.
+///
+/// Only frames where the held keys change are stored. As text, the log is a `seed` line followed by a line for
+/// each change, holding the frame number and a hex mask with a bit for each held key:
+///
+/// ```text
+/// seed 3141592653
+/// 0 0000
+/// 120 0020
+/// 135 0000
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputLog {
+    seed: u64,
+    /// Each frame where the held keys changed, along with the keys held from then on
+    changes: Vec<(u64, u16)>,
+    frame_count: u64,
+}
+
+impl InputLog {
+    pub fn new(seed: u64) -> Self {
+        InputLog { seed, changes: Vec::new(), frame_count: 0 }
+    }
+
+    /// The seed the recorded machine's random numbers were generated from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The number of frames which have been recorded.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Records the keys held down for the next frame.
+    pub fn record(&mut self, keys_pressed: &[u8]) {
+        let keys = keys_pressed.iter().filter(|&&key| key <= 0xF).fold(0, |mask, &key| mask | (1 << key));
+        if self.changes.last().map(|&(_, last_keys)| last_keys) != Some(keys) {
+            self.changes.push((self.frame_count, keys));
+        }
+        self.frame_count += 1;
+    }
+
+    /// Returns the keys which were held down during `frame`, in ascending order.
+    /// Frames past the end of the log keep the keys held in the last recorded frame.
+    pub fn keys_at(&self, frame: u64) -> Vec<u8> {
+        let change = self.changes.partition_point(|&(change_frame, _)| change_frame <= frame);
+        let keys = change.checked_sub(1).map_or(0, |change| self.changes[change].1);
+        (0..16).filter(|key| keys & (1 << key) != 0).collect()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "seed {}", self.seed);
+        for (frame, keys) in &self.changes {
+            let _ = writeln!(text, "{} {:04X}", frame, keys);
+        }
+        text
+    }
+
+    /// Reads a log written by `to_text`.
+    pub fn parse(text: &str) -> Result<Self, InputLogError> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let seed = lines.next()
+            .and_then(|(_, line)| line.trim().strip_prefix("seed "))
+            .and_then(|seed| seed.trim().parse().ok())
+            .ok_or(InputLogError::MissingSeed)?;
+
+        let mut log = InputLog::new(seed);
+        for (idx, text) in lines {
+            let line = idx + 1;
+            let (frame, keys) = text.trim().split_once(' ')
+                .and_then(|(frame, keys)| Some((frame.parse::<u64>().ok()?, u16::from_str_radix(keys.trim(), 16).ok()?)))
+                .ok_or(InputLogError::InvalidEntry { line })?;
+            if frame < log.frame_count {
+                return Err(InputLogError::OutOfOrder { line });
+            }
+            log.changes.push((frame, keys));
+            log.frame_count = frame + 1;
+        }
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_record() {
+        let mut log = InputLog::new(7);
+        for keys in [&[][..], &[], &[0x5, 0x1], &[0x1, 0x5], &[0x10], &[]] {
+            log.record(keys);
+        }
+        assert_eq!(log.frame_count(), 6);
+        assert_eq!(log.changes, [(0, 0), (2, 0x22), (4, 0)], "Only changes should be stored, ignoring invalid keys");
+        assert_eq!(log.keys_at(1), []);
+        assert_eq!(log.keys_at(3), [0x1, 0x5]);
+        assert_eq!(log.keys_at(100), []);
+    }
+
+    #[test]
+    pub fn test_text_round_trip() {
+        let mut log = InputLog::new(3141592653);
+        for keys in [&[][..], &[0xF], &[0xF, 0x0], &[]] {
+            log.record(keys);
+        }
+        let text = log.to_text();
+        assert_eq!(text, "seed 3141592653\n0 0000\n1 8000\n2 8001\n3 0000\n");
+
+        let parsed = InputLog::parse(&text).unwrap();
+        assert_eq!(parsed, log);
+
+        assert_eq!(InputLog::parse("0 0000\n"), Err(InputLogError::MissingSeed));
+        assert_eq!(InputLog::parse("seed 1\n0 00G0\n"), Err(InputLogError::InvalidEntry { line: 2 }));
+        assert_eq!(InputLog::parse("seed 1\n5 0001\n2 0000\n"), Err(InputLogError::OutOfOrder { line: 3 }));
+    }
+}
@@ -0,0 +1,125 @@
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use crate::instruction::Instruction;
+
+/// Programs with more unknown opcodes than this share of their reachable instructions likely aren't CHIP-8 programs.
+const SUSPICIOUS_UNIMPLEMENTED_RATIO: f32 = 0.1;
+/// Programs whose reachable code takes up less than this share of their bytes likely aren't CHIP-8 programs either,
+/// even with plenty of sprite data. Random bytes tend to reach an unknown opcode or a jump out of the program
+/// within a few instructions.
+const SUSPICIOUS_CODE_RATIO: f32 = 0.1;
+/// Programs with more stray jumps than this share of their reachable instructions likely aren't CHIP-8 programs.
+/// A few are allowed for, since data which the scan runs into can decode as a jump outside the program.
+const SUSPICIOUS_STRAY_JUMP_RATIO: f32 = 0.05;
+
+/// A quick check over a loaded program, returned by `Machine::analyze`, which helps tell whether a file
+/// is a CHIP-8 program at all before running it.
+///
+/// Instructions are followed from the load address through jumps, calls and skips, so sprites and other data
+/// in the program are only scanned if the code runs into them. Jumps offset by `V0` can't be followed
+/// without running the program, so code only reached through them isn't scanned.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RomReport {
+    /// The number of reachable instructions which were scanned
+    pub instruction_count: usize,
+    /// How many of those don't decode to a known instruction, which the program would crash or skip over
+    pub unimplemented_count: usize,
+    /// The size of the program in bytes, not counting any trailing zeroes
+    pub program_size: usize,
+    /// The highest address used by a jump, call, or `I` assignment, if there were any
+    pub highest_address: Option<usize>,
+    /// How many jumps and calls lead outside the program, or returns are reached outside of a subroutine,
+    /// which real programs never do
+    pub stray_jump_count: usize,
+}
+
+impl RomReport {
+    pub(crate) fn new(memory: &[u8], load_address: usize) -> Self {
+        // Trailing zeroes are left out, since they can't be told apart from the empty memory after the program.
+        let program_end = memory.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+        let mut report = RomReport {
+            instruction_count: 0,
+            unimplemented_count: 0,
+            program_size: program_end.saturating_sub(load_address),
+            highest_address: None,
+            stray_jump_count: 0,
+        };
+        let mut visited = BTreeSet::new();
+        // Each address to scan is paired with whether it's inside a subroutine, so stray returns can be counted.
+        let mut pending = vec![(load_address, false)];
+
+        while let Some((pc, is_subroutine)) = pending.pop() {
+            // Code never runs from below the load address, and an instruction needs both of its bytes in memory.
+            if pc < load_address || pc + 1 >= memory.len() || !visited.insert(pc) {
+                continue;
+            }
+            report.instruction_count += 1;
+
+            let instruction = Instruction::new(memory, pc);
+            if let Instruction::Goto { address }
+            | Instruction::CallSubroutine { address }
+            | Instruction::IStoreAddress { address }
+            | Instruction::IStoreLongAddress { address }
+            | Instruction::GotoOffsetted { address } = instruction {
+                report.highest_address = report.highest_address.max(Some(address as usize));
+            }
+            if let Instruction::Goto { address } | Instruction::CallSubroutine { address } = instruction {
+                if !(load_address..program_end).contains(&(address as usize)) {
+                    report.stray_jump_count += 1;
+                }
+            }
+            match instruction {
+                Instruction::Unimplemented { .. } => report.unimplemented_count += 1,
+                Instruction::ReturnSubroutine if !is_subroutine => report.stray_jump_count += 1,
+                Instruction::EndProgram | Instruction::ReturnSubroutine | Instruction::GotoOffsetted { .. } => {}
+                Instruction::Goto { address } => pending.push((address as usize, is_subroutine)),
+                Instruction::CallSubroutine { address } => pending.extend([(pc + 2, is_subroutine), (address as usize, true)]),
+                Instruction::SkipIfValueEq { .. }
+                | Instruction::SkipIfValueNe { .. }
+                | Instruction::SkipIfRegistersEq { .. }
+                | Instruction::SkipIfRegistersNe { .. }
+                | Instruction::SkipIfKeyOn { .. }
+                | Instruction::SkipIfKeyOff { .. } => {
+                    // Skipping over the two words of `IStoreLongAddress` lands after both of them.
+                    let skipped_size = Instruction::try_decode(memory, pc + 2).map_or(2, |skipped| skipped.size());
+                    pending.extend([(pc + 2, is_subroutine), (pc + 2 + skipped_size, is_subroutine)]);
+                }
+                _ => pending.push((pc + instruction.size(), is_subroutine)),
+            }
+        }
+        report
+    }
+
+    /// The share of scanned instructions which don't decode to a known instruction, from 0.0 to 1.0.
+    pub fn unimplemented_ratio(&self) -> f32 {
+        if self.instruction_count == 0 {
+            return 0.0;
+        }
+        self.unimplemented_count as f32 / self.instruction_count as f32
+    }
+
+    /// The share of scanned instructions which jump outside the program or return outside a subroutine,
+    /// from 0.0 to 1.0.
+    pub fn stray_jump_ratio(&self) -> f32 {
+        if self.instruction_count == 0 {
+            return 0.0;
+        }
+        self.stray_jump_count as f32 / self.instruction_count as f32
+    }
+
+    /// The share of the program's bytes taken up by reachable instructions, from 0.0 to 1.0.
+    pub fn code_ratio(&self) -> f32 {
+        if self.program_size == 0 {
+            return 0.0;
+        }
+        (self.instruction_count * 2).min(self.program_size) as f32 / self.program_size as f32
+    }
+
+    /// Whether the program is empty, often jumps outside itself, or looks too little like code to be a CHIP-8 program.
+    pub fn is_suspicious(&self) -> bool {
+        self.program_size == 0
+            || self.stray_jump_ratio() > SUSPICIOUS_STRAY_JUMP_RATIO
+            || self.unimplemented_ratio() > SUSPICIOUS_UNIMPLEMENTED_RATIO
+            || self.code_ratio() < SUSPICIOUS_CODE_RATIO
+    }
+}
@@ -0,0 +1,27 @@
+use alloc::vec::Vec;
+use crate::Quirks;
+
+/// A copy of the complete machine state, for save states.
+/// Restoring a snapshot with `Machine::restore` makes the machine behave exactly as it did when
+/// the snapshot was taken, including the quirks in use and the state of the random number generator.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MachineSnapshot {
+    pub(crate) memory: Vec<u8>,
+    pub(crate) registers: Vec<u8>,
+    pub(crate) index: usize,
+    pub(crate) program_counter: usize,
+    pub(crate) stack: Vec<usize>,
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    pub(crate) pressed_key: Option<u8>,
+    pub(crate) consumed_keys: u16,
+    pub(crate) audio_pattern: Option<[u8; 16]>,
+    pub(crate) audio_pitch: u8,
+    pub(crate) flags: [u8; 8],
+    pub(crate) is_high_res: bool,
+    pub(crate) pixels: Vec<u8>,
+    pub(crate) plane_mask: u8,
+    pub(crate) quirks: Quirks,
+    pub(crate) rng_seed: u64,
+}
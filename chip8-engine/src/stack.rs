@@ -1,28 +1,102 @@
+use alloc::vec;
+use alloc::vec::Vec;
 
-const MAX_ELEMENTS: usize = 16;
+/// CHIP-8 has room for 16 return addresses, which is also what `MachineConfig` defaults to.
+pub const DEFAULT_CAPACITY: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StackError {
+    Overflow,
+    Underflow,
+}
 
 pub struct Stack {
-    elements: [usize; MAX_ELEMENTS],
+    elements: Vec<usize>,
     pointer: usize
 }
 
 impl Stack {
+    #[cfg(test)]
     pub fn new() -> Self {
+        Stack::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates a stack which overflows once it holds more than `capacity` return addresses.
+    pub fn with_capacity(capacity: usize) -> Self {
         Stack {
-            elements: [0; MAX_ELEMENTS],
+            elements: vec![0; capacity],
             pointer: 0
         }
     }
 
-    pub fn push(&mut self, program_counter: usize) {
-        assert!(self.pointer < MAX_ELEMENTS, "Max stack size reached");
+    pub fn push(&mut self, program_counter: usize) -> Result<(), StackError> {
+        if self.pointer >= self.elements.len() {
+            return Err(StackError::Overflow);
+        }
         self.elements[self.pointer] = program_counter;
         self.pointer += 1;
+        Ok(())
+    }
+
+    pub fn depth(&self) -> usize {
+        self.pointer
     }
 
-    pub fn pop(&mut self) -> usize {
-        assert!(self.pointer > 0, "Attempt to pop from empty stack");
+    /// The return addresses currently on the stack, from the bottom up.
+    pub fn frames(&self) -> &[usize] {
+        &self.elements[..self.pointer]
+    }
+
+    pub fn pop(&mut self) -> Result<usize, StackError> {
+        if self.pointer == 0 {
+            return Err(StackError::Underflow);
+        }
         self.pointer -= 1;
-        self.elements[self.pointer]
+        Ok(self.elements[self.pointer])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_overflow() {
+        let mut stack = Stack::new();
+        for i in 0..DEFAULT_CAPACITY {
+            assert_eq!(stack.push(i * 2), Ok(()));
+        }
+
+        assert_eq!(stack.push(0xFFF), Err(StackError::Overflow));
+
+        for i in (0..DEFAULT_CAPACITY).rev() {
+            assert_eq!(stack.pop(), Ok(i * 2));
+        }
+        assert_eq!(stack.pop(), Err(StackError::Underflow));
+    }
+
+    #[test]
+    pub fn test_with_capacity() {
+        let mut stack = Stack::with_capacity(64);
+        for i in 0..64 {
+            assert_eq!(stack.push(i * 2), Ok(()), "Pushing beyond 16 frames should succeed with a larger capacity");
+        }
+        assert_eq!(stack.depth(), 64);
+        assert_eq!(stack.push(0xFFF), Err(StackError::Overflow));
+        assert_eq!(stack.pop(), Ok(63 * 2));
+    }
+
+    #[test]
+    pub fn test_frames() {
+        let mut stack = Stack::new();
+        assert!(stack.frames().is_empty());
+        for address in [0x202, 0x31A, 0x244] {
+            stack.push(address).unwrap();
+        }
+        assert_eq!(stack.depth(), 3);
+        assert_eq!(stack.frames(), &[0x202, 0x31A, 0x244]);
+
+        stack.pop().unwrap();
+        assert_eq!(stack.frames(), &[0x202, 0x31A], "Popped frames shouldn't be included");
     }
 }
@@ -2,7 +2,6 @@
 pub struct Timers {
     pub delay: u8,
     pub sound: u8,
-    time_until_tick: u8,
 }
 
 impl Timers {
@@ -10,18 +9,12 @@ impl Timers {
         Timers {
             delay: 0,
             sound: 0,
-            time_until_tick: 0,
         }
     }
 
-    pub fn tick(&mut self) {
-        if self.time_until_tick != 0 {
-            self.time_until_tick -= 1;
-            return;
-        }
-
-        self.time_until_tick = 8;
-
+    /// Counts both timers down by one. This should be called exactly once per 60Hz frame,
+    /// independent of how many instructions are executed during that frame.
+    pub fn tick_60hz(&mut self) {
         if self.delay > 0 {
             self.delay -= 1;
         }
@@ -30,4 +23,34 @@ impl Timers {
             self.sound -= 1;
         }
     }
-}
\ No newline at end of file
+
+    /// The buzzer sounds for as long as the sound timer is non-zero.
+    pub fn is_beeping(&self) -> bool {
+        self.sound > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_tick_60hz() {
+        let mut timers = Timers::new();
+        timers.delay = 60;
+        timers.sound = 30;
+
+        for _ in 0..59 {
+            timers.tick_60hz();
+        }
+        assert_eq!(timers.delay, 1);
+        assert_eq!(timers.sound, 0);
+        assert!(!timers.is_beeping());
+
+        timers.tick_60hz();
+        assert_eq!(timers.delay, 0);
+
+        timers.tick_60hz();
+        assert_eq!(timers.delay, 0, "Timers should not wrap around");
+    }
+}
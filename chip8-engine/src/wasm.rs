@@ -0,0 +1,70 @@
+use alloc::vec::Vec;
+use wasm_bindgen::prelude::*;
+
+use crate::{Machine, MachineConfig, Quirks};
+
+/// Matches the default of the desktop frontend.
+const DEFAULT_CYCLES_PER_FRAME: usize = 10;
+
+/// A machine which JavaScript can drive, e.g. rendering to a canvas from `requestAnimationFrame`.
+#[wasm_bindgen(js_name = Machine)]
+pub struct WasmMachine {
+    machine: Machine,
+    cycles: usize,
+}
+
+#[wasm_bindgen(js_class = Machine)]
+impl WasmMachine {
+    /// Loads a program, throwing if it doesn't fit in memory.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8], quirks_flag: bool) -> Result<WasmMachine, JsError> {
+        // The browser has no OS entropy source for fastrand to seed from, so borrow JavaScript's.
+        let rng = fastrand::Rng::with_seed((js_sys::Math::random() * u64::MAX as f64) as u64);
+        let machine = Machine::with_rng(bytes, Quirks::from_flag(quirks_flag), MachineConfig::default(), rng)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(WasmMachine { machine, cycles: DEFAULT_CYCLES_PER_FRAME })
+    }
+
+    /// Runs a single 60Hz frame, with `keys` holding the hex keys currently held down.
+    pub fn tick(&mut self, keys: &[u8]) -> Result<(), JsError> {
+        self.machine
+            .tick_many(keys, self.cycles)
+            .map(|_| ())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Sets the number of instructions executed per frame.
+    #[wasm_bindgen(js_name = setCycles)]
+    pub fn set_cycles(&mut self, cycles: usize) {
+        self.cycles = cycles;
+    }
+
+    /// The screen as RGBA bytes, ready to be copied into an `ImageData` of `width` by `height` pixels.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.machine.to_rgba()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.machine.resolution().0
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.machine.resolution().1
+    }
+
+    #[wasm_bindgen(js_name = isBeeping)]
+    pub fn is_beeping(&self) -> bool {
+        self.machine.is_beeping()
+    }
+
+    #[wasm_bindgen(js_name = isHalted)]
+    pub fn is_halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    pub fn reset(&mut self) {
+        self.machine.reset();
+    }
+}
@@ -5,10 +5,13 @@ pub struct Word(pub u16);
 
 impl Word {
     /// In Chip-8, words consist of two bytes in Big-Endian order.
-    /// This method loads in a word from the memory at the given address, and panics on failure.
-    pub fn new(memory: &[u8], pc: usize) -> Self {
-        assert!(pc + 1 < memory.len(), "Expecting two free bytes at pc location");
-        Word(((memory[pc] as u16) << 8) | (memory[pc + 1] as u16))
+    /// This method loads in a word from the memory at the given address, or returns `None` if the memory
+    /// ends before both of its bytes.
+    pub fn try_new(memory: &[u8], pc: usize) -> Option<Self> {
+        match memory.get(pc..pc.checked_add(2)?) {
+            Some(&[high, low]) => Some(Word(((high as u16) << 8) | (low as u16))),
+            _ => None,
+        }
     }
 
     /// Returns the first nibble of the word, used as a control bit to determine the opcode.
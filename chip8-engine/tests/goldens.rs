@@ -0,0 +1,85 @@
+//! Runs every binary program (`.ch8`) in `tests/roms` for a fixed number of frames, then compares a hash
+//! of the screen against the one stored in `tests/goldens.txt`, to catch accidental changes in behavior.
+//! The classic games bundled with the emulator live there too, so they're covered along with the test programs.
+//!
+//! Each program gets the same seed and the same keys, so a program only changes its hash when the engine does.
+//! After an intended change, the goldens are rewritten by running the test with `UPDATE_GOLDENS` set:
+//!
+//! ```text
+//! UPDATE_GOLDENS=1 cargo test --test goldens
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8_engine::{Machine, Quirks};
+
+const SEED: u64 = 0xC8;
+const FRAMES: usize = 600;
+/// Instructions per frame, matching the default of the desktop frontend.
+const CYCLES_PER_FRAME: usize = 10;
+/// Each key in turn is held for a stretch of frames, then let go for as long again.
+const FRAMES_PER_KEY: usize = 20;
+
+fn goldens_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/goldens.txt")
+}
+
+/// The keys held down during `frame`, which are the same for every program.
+fn keys_at(frame: usize) -> Vec<u8> {
+    let key = (frame / (FRAMES_PER_KEY * 2)) % 16;
+    if frame % (FRAMES_PER_KEY * 2) < FRAMES_PER_KEY { vec![key as u8] } else { Vec::new() }
+}
+
+/// FNV-1a, which is plenty to tell screens apart without adding a dependency.
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3))
+}
+
+/// Runs the program, returning the hash of its final screen. Programs which stop with an error keep the screen
+/// they stopped on, since that's part of their behavior as well.
+fn run_program(program: &[u8]) -> u64 {
+    let mut machine = Machine::with_seed(program, Quirks::inactive(), SEED);
+    for frame in 0..FRAMES {
+        if machine.tick_many(&keys_at(frame), CYCLES_PER_FRAME).is_err() {
+            break;
+        }
+    }
+    hash(machine.framebuffer())
+}
+
+#[test]
+pub fn test_goldens() {
+    let mut paths: Vec<PathBuf> = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().and_then(|extension| extension.to_str()) == Some("ch8"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "No test programs were found");
+
+    let hashes: BTreeMap<String, u64> = paths
+        .iter()
+        .map(|path| (path.file_stem().unwrap().to_string_lossy().into_owned(), run_program(&fs::read(path).unwrap())))
+        .collect();
+
+    if env::var_os("UPDATE_GOLDENS").is_some() {
+        let text: String = hashes.iter().map(|(name, hash)| format!("{} {:016x}\n", name, hash)).collect();
+        fs::write(goldens_path(), text).unwrap();
+        return;
+    }
+
+    let text = fs::read_to_string(goldens_path()).unwrap_or_default();
+    let goldens: BTreeMap<&str, &str> = text.lines().filter_map(|line| line.split_once(' ')).collect();
+    let failures: Vec<String> = hashes
+        .iter()
+        .filter_map(|(name, hash)| match goldens.get(name.as_str()) {
+            Some(golden) if *golden == format!("{:016x}", hash) => None,
+            Some(golden) => Some(format!("{}: expected a screen hash of {}, got {:016x}", name, golden, hash)),
+            None => Some(format!("{}: no golden hash is stored", name)),
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}\nRun with UPDATE_GOLDENS=1 to accept the new screens", failures.join("\n"));
+}
@@ -0,0 +1,129 @@
+//! Runs every test program in `tests/roms` until it halts, then checks the state it ended in.
+//!
+//! Programs are either assembly sources (`.asm`), or binaries (`.ch8`) such as the community test suites,
+//! with their expectations in a file of the same name ending in `.expect`. Binaries without one, like the games,
+//! are only checked by the golden screen hashes in `goldens.rs`. Expectations are comment lines:
+//!
+//! ```text
+//! ; expect V3 = 0x01
+//! ; expect I = 0x300
+//! ; quirks superchip
+//! ```
+//!
+//! The `quirks` line is optional and takes the name of a platform, otherwise the quirks are inactive.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8_engine::{assemble, Machine, Quirks, Register};
+
+/// Programs which are still running after this many instructions are treated as stuck.
+const MAX_CYCLES: usize = 1_000_000;
+
+enum Target {
+    Register(u8),
+    Index,
+}
+
+struct TestRom {
+    path: PathBuf,
+    program: Vec<u8>,
+    quirks: Quirks,
+    /// Each expected value, along with the name it was written with
+    expected: Vec<(String, Target, usize)>,
+}
+
+fn parse_number(text: &str) -> usize {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.unwrap_or_else(|_| panic!("'{}' is not a number", text))
+}
+
+fn load_rom(path: &Path) -> TestRom {
+    let (program, expectations) = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("asm") => {
+            let source = fs::read_to_string(path).unwrap();
+            let program = assemble(&source).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            (program, source)
+        }
+        _ => (fs::read(path).unwrap(), fs::read_to_string(path.with_extension("expect")).unwrap_or_default()),
+    };
+
+    let mut quirks = Quirks::inactive();
+    let mut expected = Vec::new();
+    for line in expectations.lines() {
+        let comment = line.trim().trim_start_matches(';').trim();
+        if let Some(platform) = comment.strip_prefix("quirks ") {
+            quirks = match platform.trim() {
+                "cosmac-vip" => Quirks::cosmac_vip(),
+                "chip48" => Quirks::chip48(),
+                "superchip" => Quirks::superchip(),
+                "xochip" => Quirks::xochip(),
+                platform => panic!("{}: unknown platform '{}'", path.display(), platform),
+            };
+        } else if let Some(expectation) = comment.strip_prefix("expect ") {
+            let (target, value) = expectation.split_once('=').unwrap_or_else(|| panic!("{}: invalid '{}'", path.display(), line));
+            let name = target.trim().to_string();
+            let target = match name.as_str() {
+                "I" => Target::Index,
+                register => Target::Register(parse_number(&format!("0x{}", register.trim_start_matches('V'))) as u8),
+            };
+            expected.push((name, target, parse_number(value.trim())));
+        }
+    }
+    assert!(!expected.is_empty(), "{} doesn't expect anything", path.display());
+    TestRom { path: path.to_path_buf(), program, quirks, expected }
+}
+
+/// Runs the program, returning a description of each expectation it didn't meet.
+fn run_rom(rom: &TestRom) -> Vec<String> {
+    let mut machine = Machine::try_new(&rom.program, rom.quirks).unwrap();
+    let mut cycles = 0;
+    while !machine.is_halted() {
+        if let Err(e) = machine.step(&[]) {
+            return vec![format!("stopped at {:#05x}: {}", machine.program_counter(), e)];
+        }
+        cycles += 1;
+        if cycles == MAX_CYCLES {
+            return vec![format!("still running after {} instructions", MAX_CYCLES)];
+        }
+    }
+
+    rom.expected
+        .iter()
+        .filter_map(|(name, target, expected)| {
+            let actual = match target {
+                Target::Register(register) => machine.register(Register::new(*register)) as usize,
+                Target::Index => machine.index(),
+            };
+            (actual != *expected).then(|| format!("expected {} = {:#x}, got {:#x}", name, expected, actual))
+        })
+        .collect()
+}
+
+#[test]
+pub fn test_roms() {
+    let mut paths: Vec<PathBuf> = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| match path.extension().and_then(|extension| extension.to_str()) {
+            Some("asm") => true,
+            Some("ch8") => path.with_extension("expect").exists(),
+            _ => false,
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "No test programs were found");
+
+    let failures: Vec<String> = paths
+        .iter()
+        .map(|path| load_rom(path))
+        .flat_map(|rom| {
+            let path = rom.path.file_name().unwrap().to_string_lossy().into_owned();
+            run_rom(&rom).into_iter().map(move |failure| format!("{}: {}", path, failure))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
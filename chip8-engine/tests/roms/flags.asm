@@ -0,0 +1,52 @@
+; The carry and borrow flags of 8XY4 (ADD), 8XY5 (SUB) and 8XY7 (SUBN).
+; Each result is copied out of VF before the next instruction overwrites it.
+; When VF is the target itself, the flag is written after the result and wins.
+
+    LD V1, 0x02
+
+    LD V0, 0xFF
+    ADD V0, V1      ; overflows to 0x01
+    LD V2, VF
+    LD V3, 0x10
+    ADD V3, V1      ; 0x12 fits
+    LD V4, VF
+
+    LD V5, 0x05
+    SUB V5, V1      ; 0x03 with no borrow
+    LD V6, VF
+    LD V7, 0x01
+    SUB V7, V1      ; borrows to 0xFF
+    LD V8, VF
+
+    LD V9, 0x01
+    SUBN V9, V1     ; 0x02 - 0x01 = 0x01 with no borrow
+    LD VA, VF
+    LD VB, 0x05
+    SUBN VB, V1     ; 0x02 - 0x05 borrows to 0xFD
+    LD VC, VF
+
+    LD VF, 0xFF
+    ADD VF, V1      ; the sum would be 0x01, but the carry wins
+    LD VD, VF
+    LD VF, 0x01
+    SUB VF, V1      ; the difference would be 0xFF, but the borrow wins
+    LD VE, VF
+    LD VF, 0x05
+    SUBN VF, V1     ; the difference would be 0xFD, but the borrow wins
+    END
+
+; expect V0 = 0x01
+; expect V2 = 1
+; expect V3 = 0x12
+; expect V4 = 0
+; expect V5 = 0x03
+; expect V6 = 1
+; expect V7 = 0xFF
+; expect V8 = 0
+; expect V9 = 0x01
+; expect VA = 1
+; expect VB = 0xFD
+; expect VC = 0
+; expect VD = 1
+; expect VE = 0
+; expect VF = 0
@@ -0,0 +1,26 @@
+; The flags of 8XY6 (SHR) and 8XYE (SHL), which are the bits shifted out.
+; Without the lazy shift quirk, VX is set to VY shifted, leaving VY untouched.
+
+    LD V1, 0b10000001
+
+    SHR V0, V1      ; 0b01000000, shifting out a 1
+    LD V2, VF
+    SHL V3, V0      ; 0b10000000, shifting out a 0
+    LD V4, VF
+    SHL V5, V3      ; 0b00000000, shifting out a 1
+    LD V6, VF
+
+    SHR VF, V1      ; the result would be 0x40, but the flag wins
+    LD V7, VF
+    SHL VF, V0      ; the result would be 0x80, but the flag wins
+    END
+
+; expect V0 = 0x40
+; expect V1 = 0x81
+; expect V2 = 1
+; expect V3 = 0x80
+; expect V4 = 0
+; expect V5 = 0
+; expect V6 = 1
+; expect V7 = 1
+; expect VF = 0
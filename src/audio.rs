@@ -0,0 +1,140 @@
+use std::f32::consts::TAU;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use log::warn;
+use rodio::{OutputStream, Sink, Source};
+
+const SAMPLE_RATE: u32 = 44_100;
+const BEEP_FREQUENCY: f32 = 440.0;
+
+/// The number of one-bit samples in an XO-CHIP audio pattern.
+const PATTERN_BITS: usize = 128;
+
+/// The shape of the plain tone, which is used whenever the program hasn't loaded an XO-CHIP audio pattern.
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Waveform {
+    /// A harsh buzz, as on the original CHIP-8
+    Square,
+    /// A pure, soft tone
+    Sine,
+    /// Between the two, with a hint of buzz
+    Triangle,
+}
+
+impl Waveform {
+    /// The level of the wave at `phase` through its period, from -1 to 1.
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+/// What the buzzer plays while the sound timer is active.
+#[derive(Copy, Clone)]
+enum Tone {
+    /// The plain tone, in the waveform chosen when the `Beeper` was created
+    Plain,
+    /// An XO-CHIP audio pattern, looped at `rate` samples per second
+    Pattern { pattern: [u8; 16], rate: f32 },
+}
+
+/// An endless waveform for the buzzer, which follows the tone set on the `Beeper`.
+struct Buzzer {
+    /// How far through the current period of the waveform playback is, from 0 to 1
+    phase: f32,
+    waveform: Waveform,
+    tone: Arc<Mutex<Tone>>,
+}
+
+impl Iterator for Buzzer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let tone = *self.tone.lock().unwrap();
+        let (sample, frequency) = match tone {
+            Tone::Plain => (self.waveform.sample(self.phase), BEEP_FREQUENCY),
+            Tone::Pattern { pattern, rate } => {
+                let bit = (self.phase * PATTERN_BITS as f32) as usize % PATTERN_BITS;
+                let is_high = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                (if is_high { 1.0 } else { -1.0 }, rate / PATTERN_BITS as f32)
+            }
+        };
+        self.phase = (self.phase + frequency / SAMPLE_RATE as f32) % 1.0;
+        Some(sample)
+    }
+}
+
+impl Source for Buzzer {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+pub struct Beeper {
+    // The stream has to outlive the sink, otherwise playback stops.
+    _stream: OutputStream,
+    sink: Sink,
+    tone: Arc<Mutex<Tone>>,
+}
+
+impl Beeper {
+    /// Opens the default audio device, or returns `None` if there isn't one available.
+    /// The plain tone is played as `waveform`, and everything is played at `volume`, from 0.0 (silent) to 1.0.
+    pub fn new(waveform: Waveform, volume: f32) -> Option<Self> {
+        let (stream, handle) = match OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Unable to open audio output, sound is disabled: {}", e);
+                return None;
+            }
+        };
+
+        let sink = match Sink::try_new(&handle) {
+            Ok(sink) => sink,
+            Err(e) => {
+                warn!("Unable to create audio sink, sound is disabled: {}", e);
+                return None;
+            }
+        };
+        let tone = Arc::new(Mutex::new(Tone::Plain));
+        sink.pause();
+        sink.set_volume(volume);
+        sink.append(Buzzer { phase: 0.0, waveform, tone: tone.clone() });
+
+        Some(Beeper { _stream: stream, sink, tone })
+    }
+
+    /// Plays an XO-CHIP audio pattern at `rate` samples per second, or the plain tone when there's no pattern.
+    pub fn set_pattern(&self, pattern: Option<[u8; 16]>, rate: f32) {
+        let tone = match pattern {
+            Some(pattern) => Tone::Pattern { pattern, rate },
+            None => Tone::Plain,
+        };
+        *self.tone.lock().unwrap() = tone;
+    }
+
+    pub fn set_beeping(&self, is_beeping: bool) {
+        if is_beeping {
+            self.sink.play();
+        } else {
+            self.sink.pause();
+        }
+    }
+}
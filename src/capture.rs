@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{self, FilterType};
+use image::{Delay, Frame, ImageResult, RgbaImage};
+
+use chip8_engine::Machine;
+
+/// CHIP-8 screens are tiny, so captures are scaled up to stay legible.
+/// Both resolutions divide evenly into this, which lets high-res frames sit alongside low-res ones in a recording.
+const CAPTURE_SIZE: (u32, u32) = (640, 320);
+
+/// Browsers slow down GIF frames shorter than 20ms, so only every third 60Hz frame is kept.
+const RECORDING_FRAME_SKIP: usize = 3;
+const RECORDING_FRAME_DELAY_MS: u32 = 50;
+
+/// Recordings stop growing after this many kept frames (one minute of gameplay).
+const MAX_RECORDING_FRAMES: usize = 60 * 1000 / RECORDING_FRAME_DELAY_MS as usize;
+
+/// Writes the current frame to a timestamped PNG in the working directory, returning its path.
+pub fn save_screenshot(machine: &Machine) -> ImageResult<String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let path = format!("crust-{}.png", timestamp);
+    capture_frame(machine).save(&path)?;
+    Ok(path)
+}
+
+fn capture_frame(machine: &Machine) -> RgbaImage {
+    let (width, height) = machine.resolution();
+    let frame = RgbaImage::from_raw(width as u32, height as u32, machine.to_rgba())
+        .expect("Framebuffer size should match the resolution");
+    imageops::resize(&frame, CAPTURE_SIZE.0, CAPTURE_SIZE.1, FilterType::Nearest)
+}
+
+/// Collects frames while the emulator runs, to be written out as an animated GIF.
+pub struct Recorder {
+    path: String,
+    frames: Vec<RgbaImage>,
+    frame_count: usize,
+}
+
+impl Recorder {
+    pub fn new(path: String) -> Self {
+        Recorder { path, frames: Vec::new(), frame_count: 0 }
+    }
+
+    /// Called once per 60Hz update; frames past the recording cap are dropped.
+    pub fn capture(&mut self, machine: &Machine) {
+        if self.frame_count.is_multiple_of(RECORDING_FRAME_SKIP) && self.frames.len() < MAX_RECORDING_FRAMES {
+            self.frames.push(capture_frame(machine));
+        }
+        self.frame_count += 1;
+    }
+
+    /// Encodes the collected frames, returning the path they were written to.
+    pub fn save(self) -> ImageResult<String> {
+        let mut encoder = GifEncoder::new_with_speed(File::create(&self.path)?, 30);
+        encoder.set_repeat(Repeat::Infinite)?;
+        let delay = Delay::from_numer_denom_ms(RECORDING_FRAME_DELAY_MS, 1);
+        encoder.encode_frames(self.frames.into_iter().map(|frame| Frame::from_parts(frame, 0, 0, delay)))?;
+        Ok(self.path)
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Instant;
+
+/// The delay and sound timers count down at 60Hz, regardless of the CPU frequency.
+const TIMER_FREQUENCY: f64 = 60.0;
+
+/// Time lost to a stall (e.g. while the window is being dragged) is only made up to this limit,
+/// so the emulator doesn't race through seconds of gameplay to catch up.
+const MAX_ELAPSED_SECS: f64 = 0.25;
+
+/// Schedules frames by measuring the time between updates, rather than assuming each update takes exactly 1/60th
+/// of a second. Over time this runs `frequency` instructions and 60 timer ticks for every second of real time.
+pub struct WallClock {
+    frequency: f64,
+    last_update: Instant,
+    pending_cycles: f64,
+    pending_frames: f64,
+}
+
+impl WallClock {
+    pub fn new(frequency: u32) -> Self {
+        WallClock {
+            frequency: frequency as f64,
+            last_update: Instant::now(),
+            pending_cycles: 0.0,
+            pending_frames: 0.0,
+        }
+    }
+
+    /// The number of instructions in an average frame.
+    pub fn cycles_per_frame(&self) -> usize {
+        (self.frequency / TIMER_FREQUENCY).round() as usize
+    }
+
+    /// Forgets the time since the last update, so a pause isn't made up for once it's over.
+    pub fn skip(&mut self) {
+        self.last_update = Instant::now();
+    }
+
+    /// Returns the number of instructions to run in each frame which has come due since the last update,
+    /// with time passing `speed` times faster than real time.
+    pub fn frames_due(&mut self, speed: f64) -> Vec<usize> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f64().min(MAX_ELAPSED_SECS) * speed;
+        self.last_update = now;
+        self.pending_cycles += elapsed * self.frequency;
+        self.pending_frames += elapsed * TIMER_FREQUENCY;
+
+        let frame_count = self.pending_frames.floor() as usize;
+        self.pending_frames -= frame_count as f64;
+
+        // Spread the instructions which are due evenly over the frames, carrying any fraction over to later ones.
+        (0..frame_count)
+            .map(|frame| {
+                let cycles = (self.pending_cycles / (frame_count - frame) as f64).floor();
+                self.pending_cycles -= cycles;
+                cycles as usize
+            })
+            .collect()
+    }
+}
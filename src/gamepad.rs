@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use gilrs::{Button, Gilrs};
+use log::warn;
+
+pub struct Gamepads {
+    gilrs: Gilrs,
+    mappings: HashMap<Button, u8>,
+}
+
+impl Gamepads {
+    /// Starts listening for gamepads, or returns `None` if gamepad input isn't available.
+    pub fn new(mappings: HashMap<Button, u8>) -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Gamepads { gilrs, mappings }),
+            Err(e) => {
+                warn!("Unable to initialize gamepad input, gamepads are disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Returns the keys held down on any connected gamepad.
+    pub fn get_keys_pressed(&mut self) -> Vec<u8> {
+        // Drain the pending events, so the gamepad states are up to date.
+        while self.gilrs.next_event().is_some() {}
+
+        let gilrs = &self.gilrs;
+        self.mappings
+            .iter()
+            .filter(|m| gilrs.gamepads().any(|(_, gamepad)| gamepad.is_pressed(*m.0)))
+            .map(|m| *m.1)
+            .collect()
+    }
+}
+
+/// The default layout puts the keys commonly used for movement (2, 4, 6, 8) on the d-pad,
+/// and the common action key (5) on the bottom face button.
+pub fn get_default_button_mappings() -> HashMap<Button, u8> {
+    HashMap::from([
+        (Button::East,          0x0),
+        (Button::LeftTrigger,   0x1),
+        (Button::DPadUp,        0x2),
+        (Button::RightThumb,    0x3),
+        (Button::DPadLeft,      0x4),
+        (Button::South,         0x5),
+        (Button::DPadRight,     0x6),
+        (Button::West,          0x7),
+        (Button::DPadDown,      0x8),
+        (Button::North,         0x9),
+        (Button::LeftTrigger2,  0xA),
+        (Button::Select,        0xB),
+        (Button::RightTrigger,  0xC),
+        (Button::RightTrigger2, 0xD),
+        (Button::LeftThumb,     0xE),
+        (Button::Start,         0xF)
+    ])
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::hash::Hash;
+
+use gilrs::Button;
+use serde::de::DeserializeOwned;
+use winit::event::VirtualKeyCode;
+
+/// Loads a keyboard mapping from a TOML file, which maps each of the 16 hex keys to a winit key name:
+/// ```toml
+/// 0 = "X"
+/// 1 = "Key1"
+/// # ...
+/// F = "V"
+/// ```
+pub fn load_keymap(path: &str) -> Result<HashMap<VirtualKeyCode, u8>, String> {
+    load_mappings(path, "Keymap")
+}
+
+/// Loads a gamepad mapping from a TOML file in the same format as `load_keymap`, with gilrs button names:
+/// ```toml
+/// 0 = "East"
+/// 1 = "LeftTrigger"
+/// # ...
+/// F = "Start"
+/// ```
+pub fn load_gamepad_map(path: &str) -> Result<HashMap<Button, u8>, String> {
+    load_mappings(path, "Gamepad map")
+}
+
+/// Loads a mapping from each input named in the TOML file to its hex key, with errors starting with `kind`.
+fn load_mappings<K: DeserializeOwned + Copy + Eq + Hash + Debug>(path: &str, kind: &str) -> Result<HashMap<K, u8>, String> {
+    let lowercase_kind = kind.to_lowercase();
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read {} '{}': {}", lowercase_kind, path, e))?;
+    let entries: HashMap<String, K> = toml::from_str(&contents)
+        .map_err(|e| format!("Unable to parse {} '{}': {}", lowercase_kind, path, e))?;
+
+    let mut mappings = HashMap::new();
+    for (key, input) in entries {
+        let value = u8::from_str_radix(&key, 16)
+            .ok()
+            .filter(|&value| key.len() == 1 && value <= 0xF)
+            .ok_or_else(|| format!("{} entry '{}' is not a hex key from 0 to F", kind, key))?;
+        if let Some(other) = mappings.insert(input, value) {
+            return Err(format!("{} assigns {:?} to both key {:X} and key {:X}", kind, input, other, value));
+        }
+    }
+
+    check_all_keys(&mappings).map_err(|missing| format!("{} '{}' is missing keys: {}", kind, path, missing))?;
+    Ok(mappings)
+}
+
+/// Checks that every one of the 16 hex keys has an input, returning the missing ones as a list like `"3, A"`.
+pub fn check_all_keys<K>(mappings: &HashMap<K, u8>) -> Result<(), String> {
+    let missing: Vec<String> = (0..=0xFu8)
+        .filter(|value| !mappings.values().any(|v| v == value))
+        .map(|value| format!("{:X}", value))
+        .collect();
+    if !missing.is_empty() {
+        return Err(missing.join(", "));
+    }
+    Ok(())
+}
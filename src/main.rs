@@ -1,25 +1,116 @@
+mod audio;
+mod capture;
+mod clock;
+mod gamepad;
+mod keymap;
+mod trace;
+
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs;
-use std::time::Duration;
-use clap::{Parser};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use clap::{Parser, ValueEnum};
 
 use game_loop::{game_loop, Time, TimeTrait};
 use log::error;
 use pixels::{Pixels, SurfaceTexture};
-use winit::dpi::LogicalSize;
+use winit::dpi::{LogicalSize, PhysicalSize};
 use winit::event::VirtualKeyCode;
 use winit::event_loop::EventLoop;
 use winit::window::{Window, WindowBuilder};
 use winit_input_helper::WinitInputHelper;
 
-use chip8_engine::{Machine, Quirks};
+use chip8_engine::{InputLog, Instruction, Machine, MachineError, Quirks, FLAG_COUNT};
+
+use crate::audio::{Beeper, Waveform};
+use crate::capture::Recorder;
+use crate::clock::WallClock;
+use crate::gamepad::Gamepads;
+use crate::trace::Tracer;
+
+const FRAMES_PER_SECOND: u32 = 60;
+const TIME_STEP: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND as u64);
+
+/// The speed multiplier is doubled or halved by the speed controls, within this range.
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 8.0;
+
+/// The brightness controls step by this much, stopping short of a fully black screen.
+const BRIGHTNESS_STEP: f32 = 0.25;
+const MIN_BRIGHTNESS: f32 = 0.25;
+
+/// The window starts out this many times the size of the low resolution display.
+const WINDOW_SCALE: usize = 10;
+
+/// The number of frames run by `--turbo`, which is a few minutes of gameplay at the default cycles per frame.
+const TURBO_FRAMES: usize = 10_000;
 
-const TIME_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60 as u64);
+/// The number of bytes on each line printed by `--dump-memory`.
+const MEMORY_DUMP_ROW_SIZE: usize = 16;
 
 struct Emulator {
     machine: Machine,
+    cycles: usize,
     pixels: Pixels,
+    resolution: (usize, usize),
+    keyboard_mappings: HashMap<VirtualKeyCode, u8>,
+    /// Set when the window surface is resized, so the next frame is presented even if the screen hasn't changed
+    is_surface_stale: bool,
     input: WinitInputHelper,
+    gamepads: Option<Gamepads>,
+    beeper: Option<Beeper>,
+    paused: bool,
+    advance_frame: bool,
+    recorder: Option<Recorder>,
+    flags_path: Option<String>,
+    /// How many machine frames run per 60Hz update, so the timers speed up along with the program
+    speed: f64,
+    /// Machine frames owed to the speed multiplier, which builds up over several updates when slowed down
+    pending_frames: f64,
+    /// Replaces `cycles` and `pending_frames` when frames are timed against the wall clock
+    clock: Option<WallClock>,
+    /// The memory range to print on exit, as `(start, len)`
+    dump_memory: Option<(usize, usize)>,
+    replay: Option<Replay>,
+    /// Shared with the machine's trace callback, so the last instruction can be written out on exit
+    tracer: Option<Rc<RefCell<Tracer>>>,
+}
+
+/// Keeps track of the keys held in each machine frame, for `--record-input` and `--replay`.
+enum Replay {
+    /// Keys are read as usual and logged, then the log is written to `path` on exit
+    Record { path: String, log: InputLog },
+    /// Keys are taken from the log instead of the keyboard and gamepads
+    Playback { log: InputLog, frame: u64 },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Platform {
+    CosmacVip,
+    Chip48,
+    Superchip,
+    Xochip,
+}
+
+impl Platform {
+    fn quirks(self) -> Quirks {
+        match self {
+            Platform::CosmacVip => Quirks::cosmac_vip(),
+            Platform::Chip48 => Quirks::chip48(),
+            Platform::Superchip => Quirks::superchip(),
+            Platform::Xochip => Quirks::xochip(),
+        }
+    }
+}
+
+/// The built-in keyboard layouts, which can be swapped for a custom one with --keymap.
+#[derive(Copy, Clone, ValueEnum)]
+enum Layout {
+    /// The keypad on the left side of the keyboard, from 1 to V
+    Player1,
+    /// The keypad on the right side of the keyboard, from 7 to /, so a second player can share the keyboard
+    Player2,
 }
 
 #[derive(Parser)]
@@ -32,41 +123,311 @@ struct Cli {
     /// Whether quirks mode should be active (required for some games to work)
     #[clap(short, long, action)]
     quirks: bool,
+
+    /// Use the quirks of a specific platform, instead of toggling them with --quirks
+    #[clap(short, long, value_enum, conflicts_with = "quirks")]
+    platform: Option<Platform>,
+
+    /// Number of instructions to execute per frame (at 60 frames per second)
+    #[clap(short, long, value_parser, default_value_t = 10)]
+    cycles: usize,
+
+    /// Time the CPU and timers against the wall clock, running --frequency instructions per second instead of --cycles per frame
+    #[clap(long, action)]
+    wall_clock: bool,
+
+    /// Instructions per second when timing against the wall clock. The default of 600Hz matches the default --cycles
+    #[clap(long, value_parser, default_value_t = 600)]
+    frequency: u32,
+
+    /// Color of lit pixels, as a hex RGB value
+    #[clap(long, value_parser = parse_color, default_value = "0080FF")]
+    fg: (u8, u8, u8),
+
+    /// Color of unlit pixels, as a hex RGB value
+    #[clap(long, value_parser = parse_color, default_value = "002142")]
+    bg: (u8, u8, u8),
+
+    /// Number of frames pixels take to fade out after turning off, which reduces flicker
+    #[clap(long, value_parser, default_value_t = 0)]
+    persistence: u8,
+
+    /// Path to a TOML file mapping each hex key (0-F) to a keyboard key name
+    #[clap(short, long, value_parser)]
+    keymap: Option<String>,
+
+    /// Path to a TOML file mapping each hex key (0-F) to a gamepad button name
+    #[clap(long, value_parser, value_name = "PATH")]
+    gamepad_map: Option<String>,
+
+    /// Keyboard layout to use when no --keymap is given
+    #[clap(long, value_enum, default_value = "player1", conflicts_with = "keymap")]
+    layout: Layout,
+
+    /// Shape of the beep, for programs which don't load their own XO-CHIP audio pattern
+    #[clap(long, value_enum, default_value = "square")]
+    waveform: Waveform,
+
+    /// Volume of the beep, as a percentage
+    #[clap(long, value_parser = clap::value_parser!(u8).range(0..=100), default_value_t = 25)]
+    volume: u8,
+
+    /// Record gameplay to an animated GIF, written on exit (capped at one minute)
+    #[clap(short, long, value_parser)]
+    record: Option<String>,
+
+    /// Run as fast as possible without a window, then print the instructions per second and exit
+    #[clap(long, action)]
+    turbo: bool,
+
+    /// Write every executed instruction to a file, along with the registers it changed
+    #[clap(long, value_parser, value_name = "PATH")]
+    trace: Option<String>,
+
+    /// Print a listing of the program's instructions and exit, without running it
+    #[clap(long, action)]
+    disassemble: bool,
+
+    /// Path to a file which keeps the SUPER-CHIP user flags between runs, created on exit if it doesn't exist
+    #[clap(long, value_parser)]
+    flags: Option<String>,
+
+    /// Print a hex dump of memory on exit, as a hex start address and length like 200:40
+    #[clap(long, value_parser = parse_memory_range, value_name = "START:LEN")]
+    dump_memory: Option<(usize, usize)>,
+
+    /// Record the keys held in each frame to a file, written on exit, so the session can be played back with --replay
+    #[clap(long, value_parser, value_name = "PATH", conflicts_with = "replay")]
+    record_input: Option<String>,
+
+    /// Play back the keys recorded with --record-input, instead of reading the keyboard
+    #[clap(long, value_parser, value_name = "PATH")]
+    replay: Option<String>,
 }
 
 fn main() {
     env_logger::init();
 
     let cli = Cli::parse();
-    let program_bytes = fs::read(cli.path).expect("Unable to find input file");
-    let machine = Machine::new(program_bytes, Quirks::from_flag(cli.quirks));
+    let program = fs::File::open(&cli.path).expect("Unable to find input file");
+    let quirks = match cli.platform {
+        Some(platform) => platform.quirks(),
+        None => Quirks::from_flag(cli.quirks),
+    };
+    let mut machine = Machine::from_reader(program, quirks).unwrap_or_else(|e| {
+        eprintln!("Unable to load '{}': {}", cli.path, e);
+        std::process::exit(1);
+    });
+    if machine.analyze().is_suspicious() {
+        eprintln!("Warning: '{}' doesn't look like a CHIP-8 program", cli.path);
+    }
+    if cli.disassemble {
+        print_disassembly(&machine);
+        return;
+    }
+    machine.set_colors(cli.fg, cli.bg);
+    machine.set_persistence(cli.persistence);
+    if let Some(path) = &cli.flags {
+        machine.set_flags(load_flags(path));
+    }
+    let replay = match (cli.record_input, &cli.replay) {
+        (Some(path), _) => {
+            let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+            machine.set_seed(seed);
+            Some(Replay::Record { path, log: InputLog::new(seed) })
+        }
+        (None, Some(path)) => {
+            let log = load_input_log(path);
+            machine.set_seed(log.seed());
+            Some(Replay::Playback { log, frame: 0 })
+        }
+        (None, None) => None,
+    };
+
+    let tracer = cli.trace.as_ref().map(|path| {
+        let tracer = Tracer::new(path).unwrap_or_else(|e| {
+            eprintln!("Unable to create trace '{}': {}", path, e);
+            std::process::exit(1);
+        });
+        let tracer = Rc::new(RefCell::new(tracer));
+        let tracer_by_callback = Rc::clone(&tracer);
+        machine.set_trace_callback(Box::new(move |machine, pc, instruction| {
+            tracer_by_callback.borrow_mut().trace(machine, pc, instruction);
+        }));
+        tracer
+    });
+
+    if cli.turbo {
+        let result = run_turbo(&mut machine, cli.cycles);
+        if let Some(tracer) = &tracer {
+            tracer.borrow_mut().finish(&machine);
+        }
+        if let Err(e) = result {
+            eprintln!("machine.tick() failed: {}", e);
+            std::process::exit(1);
+        }
+        if let Some(range) = cli.dump_memory {
+            print_memory_dump(&machine, range);
+        }
+        return;
+    }
 
     let event_loop = EventLoop::new();
-    let window = get_window(&event_loop);
-    let pixels = get_pixels(&window);
+    let resolution = machine.resolution();
+    let window = get_window(&event_loop, resolution);
+    let pixels = get_pixels(&window, resolution);
 
-    let keyboard_mappings = get_keyboard_mappings();
+    let keyboard_mappings = match &cli.keymap {
+        Some(path) => keymap::load_keymap(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => {
+            let mappings = get_keyboard_mappings(cli.layout);
+            keymap::check_all_keys(&mappings).expect("Built-in layout is missing keys");
+            mappings
+        }
+    };
     let input = WinitInputHelper::new();
+    let button_mappings = match &cli.gamepad_map {
+        Some(path) => keymap::load_gamepad_map(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => {
+            let mappings = gamepad::get_default_button_mappings();
+            keymap::check_all_keys(&mappings).expect("Built-in gamepad mapping is missing keys");
+            mappings
+        }
+    };
+    let gamepads = Gamepads::new(button_mappings);
+    let beeper = Beeper::new(cli.waveform, cli.volume as f32 / 100.0);
 
-    let emulator = Emulator { machine, pixels, input };
+    let emulator = Emulator {
+        machine,
+        cycles: cli.cycles,
+        pixels,
+        resolution,
+        keyboard_mappings,
+        is_surface_stale: false,
+        input,
+        gamepads,
+        beeper,
+        paused: false,
+        advance_frame: false,
+        recorder: cli.record.map(Recorder::new),
+        flags_path: cli.flags,
+        speed: 1.0,
+        pending_frames: 0.0,
+        clock: cli.wall_clock.then(|| WallClock::new(cli.frequency)),
+        dump_memory: cli.dump_memory,
+        replay,
+        tracer,
+    };
 
     game_loop(
         event_loop,
         window,
         emulator,
-        480,
+        FRAMES_PER_SECOND,
         0.1,
         move |g| {
-            let keys_pressed = get_keys_pressed(&g.game.input, &keyboard_mappings);
-            g.game.machine.tick(keys_pressed);
+            // While paused, only run a frame when one has been requested.
+            if g.game.paused && !g.game.advance_frame {
+                if let Some(beeper) = &g.game.beeper {
+                    beeper.set_beeping(false);
+                }
+                if let Some(clock) = &mut g.game.clock {
+                    clock.skip();
+                }
+                return;
+            }
+            // Stepping through frames ignores the speed, so each step is exactly one frame.
+            let frames = match &mut g.game.clock {
+                Some(clock) if g.game.advance_frame => vec![clock.cycles_per_frame()],
+                Some(clock) => clock.frames_due(g.game.speed),
+                None => {
+                    g.game.pending_frames = if g.game.advance_frame { 1.0 } else { g.game.pending_frames + g.game.speed };
+                    let frame_count = g.game.pending_frames.floor();
+                    g.game.pending_frames -= frame_count;
+                    vec![g.game.cycles; frame_count as usize]
+                }
+            };
+            g.game.advance_frame = false;
+
+            let mut keys_pressed = get_keys_pressed(&g.game.input, &g.game.keyboard_mappings);
+            if let Some(gamepads) = &mut g.game.gamepads {
+                keys_pressed.extend(gamepads.get_keys_pressed());
+                keys_pressed.sort_unstable();
+                keys_pressed.dedup();
+            }
+            for cycles in frames {
+                let keys_pressed = match &mut g.game.replay {
+                    Some(Replay::Record { log, .. }) => {
+                        log.record(&keys_pressed);
+                        keys_pressed.clone()
+                    }
+                    Some(Replay::Playback { log, frame }) => {
+                        *frame += 1;
+                        log.keys_at(*frame - 1)
+                    }
+                    None => keys_pressed.clone(),
+                };
+                // Input is latched once per frame, so every instruction in the frame sees the same keys.
+                g.game.machine.set_keys(&keys_pressed);
+                if let Err(e) = g.game.machine.tick_many(&[], cycles) {
+                    error!("machine.tick() failed: {}", e);
+                    shut_down(&mut g.game);
+                    g.exit();
+                    return;
+                }
+            }
+            if let Some(beeper) = &g.game.beeper {
+                beeper.set_pattern(g.game.machine.audio_pattern(), g.game.machine.audio_playback_rate());
+                beeper.set_beeping(g.game.machine.is_beeping());
+            }
+            if let Some(recorder) = &mut g.game.recorder {
+                recorder.capture(&g.game.machine);
+            }
         },
         move |g| {
-            let title = format!("UPS {}, FPS {}", g.updates_per_second, (1f64 / g.last_frame_time()) as u8);
+            let fps = (1f64 / g.last_frame_time()) as u8;
+            let mut title = format!("UPS {}, FPS {}, Speed {}x", g.updates_per_second, fps, g.game.speed);
+            if g.game.paused {
+                title.push_str(" - PAUSED");
+            }
+            if g.game.machine.is_halted() {
+                title.push_str(" - HALTED");
+            }
+            // Shown even without an audio device, to check the sound timer by eye.
+            if g.game.machine.is_beeping() {
+                title.push_str(" - BEEP");
+            }
             g.window.set_title(&title);
-            g.game.machine.draw(g.game.pixels.get_frame());
-            if let Err(e) = g.game.pixels.render() {
-                error!("pixels.render() failed: {:?}", e);
-                g.exit();
+
+            // SUPER-CHIP programs can switch between resolutions at any time.
+            let resolution = g.game.machine.resolution();
+            let is_resized = resolution != g.game.resolution;
+            if is_resized {
+                g.game.pixels.resize_buffer(resolution.0 as u32, resolution.1 as u32);
+                g.game.resolution = resolution;
+                fit_window(&g.window, resolution);
+            }
+            // Presenting is skipped while the screen is unchanged, e.g. for programs waiting on a key.
+            // A resized buffer holds none of the old screen, so it's always drawn in full.
+            let is_drawn = if is_resized {
+                g.game.machine.draw(g.game.pixels.get_frame());
+                true
+            } else {
+                g.game.machine.draw_if_dirty(g.game.pixels.get_frame())
+            };
+            if is_drawn || g.game.is_surface_stale {
+                g.game.is_surface_stale = false;
+                if let Err(e) = g.game.pixels.render() {
+                    error!("pixels.render() failed: {:?}", e);
+                    shut_down(&mut g.game);
+                    g.exit();
+                }
             }
 
             // Sleep the main thread to limit drawing to the fixed time step.
@@ -78,25 +439,192 @@ fn main() {
         },
         |g, event| {
             let input = &mut g.game.input;
+            let mappings = &g.game.keyboard_mappings;
             if input.update(event) {
                 // Close events
-                if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+                if control_pressed(input, mappings, VirtualKeyCode::Escape) || input.quit() {
+                    shut_down(&mut g.game);
                     g.exit();
                     return;
                 }
 
+                // Pause controls
+                if control_pressed(input, mappings, VirtualKeyCode::Space) {
+                    g.game.paused = !g.game.paused;
+                }
+                if control_pressed(input, mappings, VirtualKeyCode::N) && g.game.paused {
+                    g.game.advance_frame = true;
+                }
+
+                // Speed controls
+                if control_pressed(input, mappings, VirtualKeyCode::Equals) || control_pressed(input, mappings, VirtualKeyCode::NumpadAdd) {
+                    g.game.speed = (g.game.speed * 2.0).min(MAX_SPEED);
+                }
+                if control_pressed(input, mappings, VirtualKeyCode::Minus) || control_pressed(input, mappings, VirtualKeyCode::NumpadSubtract) {
+                    g.game.speed = (g.game.speed / 2.0).max(MIN_SPEED);
+                }
+
+                // Display controls
+                if control_pressed(input, mappings, VirtualKeyCode::I) {
+                    let is_inverted = g.game.machine.is_inverted();
+                    g.game.machine.set_inverted(!is_inverted);
+                }
+                if control_pressed(input, mappings, VirtualKeyCode::RBracket) {
+                    let brightness = g.game.machine.brightness();
+                    g.game.machine.set_brightness(brightness + BRIGHTNESS_STEP);
+                }
+                if control_pressed(input, mappings, VirtualKeyCode::LBracket) {
+                    let brightness = g.game.machine.brightness();
+                    g.game.machine.set_brightness((brightness - BRIGHTNESS_STEP).max(MIN_BRIGHTNESS));
+                }
+
+                // Screenshots
+                if control_pressed(input, mappings, VirtualKeyCode::F12) {
+                    match capture::save_screenshot(&g.game.machine) {
+                        Ok(path) => println!("Saved screenshot to {}", path),
+                        Err(e) => error!("Unable to save screenshot: {}", e),
+                    }
+                }
+
                 // Resize the window
                 if let Some(size) = input.window_resized() {
                     g.game.pixels.resize_surface(size.width, size.height);
+                    g.game.is_surface_stale = true;
                 }
             }
         }
     );
 }
 
-fn get_window(event_loop: &EventLoop<()>) -> Window {
-    let output_size = LogicalSize::new(64 as f64, 32 as f64);
-    let window_size = LogicalSize::new(640 as f64, 320 as f64);
+/// Benchmarks the engine by running a fixed number of frames back to back, with no input held down.
+fn run_turbo(machine: &mut Machine, cycles: usize) -> Result<(), MachineError> {
+    let start = Instant::now();
+    for _ in 0..TURBO_FRAMES {
+        machine.tick_many(&[], cycles)?;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "Executed {} instructions over {} frames in {:.3}s ({:.0} instructions per second)",
+        machine.cycles(), TURBO_FRAMES, elapsed, machine.cycles() as f64 / elapsed
+    );
+    Ok(())
+}
+
+/// Writes out anything which is kept after the emulator exits.
+fn shut_down(emulator: &mut Emulator) {
+    if let Some(recorder) = emulator.recorder.take() {
+        match recorder.save() {
+            Ok(path) => println!("Saved recording to {}", path),
+            Err(e) => error!("Unable to save recording: {}", e),
+        }
+    }
+    if let Some(path) = &emulator.flags_path {
+        if let Err(e) = fs::write(path, emulator.machine.flags()) {
+            error!("Unable to save flags to '{}': {}", path, e);
+        }
+    }
+    if let Some(Replay::Record { path, log }) = &emulator.replay {
+        match fs::write(path, log.to_text()) {
+            Ok(()) => println!("Saved input log to {}", path),
+            Err(e) => error!("Unable to save input log to '{}': {}", path, e),
+        }
+    }
+    if let Some(range) = emulator.dump_memory {
+        print_memory_dump(&emulator.machine, range);
+    }
+    if let Some(tracer) = &emulator.tracer {
+        tracer.borrow_mut().finish(&emulator.machine);
+    }
+}
+
+/// Prints memory as a hex dump. Each line holds the address and 16 bytes, followed by the bytes as ASCII
+/// with unprintable ones shown as `.`.
+fn print_memory_dump(machine: &Machine, (start, len): (usize, usize)) {
+    let bytes = match machine.memory_dump(start, len) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Unable to dump memory: {}", e);
+            return;
+        }
+    };
+    for (row, chunk) in bytes.chunks(MEMORY_DUMP_ROW_SIZE).enumerate() {
+        let hex: String = chunk.iter().map(|byte| format!(" {:02X}", byte)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        // Short rows are padded so the ASCII column lines up with the rows above.
+        let address = start + row * MEMORY_DUMP_ROW_SIZE;
+        println!("{:04X} {:<width$}  |{}|", address, hex, ascii, width = MEMORY_DUMP_ROW_SIZE * 3);
+    }
+}
+
+/// Prints each word of the program from its entry point, like `0x200: 0x6005  LD V0, 0x05`.
+/// Runs of words which aren't instructions are most likely sprites or other data, so they're marked as such.
+fn print_disassembly(machine: &Machine) {
+    let start = machine.program_counter();
+    let listing = machine.disassemble_range(start, start + machine.analyze().program_size);
+    let is_unknown = |index: usize| {
+        matches!(listing.get(index), Some((_, Instruction::Unimplemented { .. }, _)))
+    };
+    for (index, (address, instruction, text)) in listing.iter().enumerate() {
+        let word = machine.read_memory(*address, 2).map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+        let mut line = format!("{:#05x}: {:#06X}  {}", address, word, text);
+        if let Instruction::Unimplemented { .. } = instruction {
+            let is_data = is_unknown(index + 1) || index.checked_sub(1).is_some_and(is_unknown);
+            line = format!("{:<30}; {}", line, if is_data { "data" } else { "unknown opcode" });
+        }
+        println!("{}", line);
+    }
+}
+
+/// Reads the user flags saved by a previous run, which start out cleared if there's no file yet.
+fn load_flags(path: &str) -> [u8; FLAG_COUNT] {
+    let mut flags = [0; FLAG_COUNT];
+    match fs::read(path) {
+        Ok(bytes) => {
+            let len = bytes.len().min(FLAG_COUNT);
+            flags[..len].copy_from_slice(&bytes[..len]);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            eprintln!("Unable to read flags from '{}': {}", path, e);
+            std::process::exit(1);
+        }
+    }
+    flags
+}
+
+fn load_input_log(path: &str) -> InputLog {
+    let text = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Unable to read input log '{}': {}", path, e);
+        std::process::exit(1);
+    });
+    InputLog::parse(&text).unwrap_or_else(|e| {
+        eprintln!("Unable to read input log '{}': {}", path, e);
+        std::process::exit(1);
+    })
+}
+
+fn parse_color(hex: &str) -> Result<(u8, u8, u8), String> {
+    let hex = hex.trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("'{}' is not a six digit hex color like 0080FF", hex))?;
+    Ok(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+fn parse_memory_range(range: &str) -> Result<(usize, usize), String> {
+    let parse_hex = |hex: &str| usize::from_str_radix(hex.trim_start_matches("0x"), 16).ok();
+    range.split_once(':')
+        .and_then(|(start, len)| Some((parse_hex(start)?, parse_hex(len)?)))
+        .ok_or_else(|| format!("'{}' is not a hex start address and length like 200:40", range))
+}
+
+fn get_window(event_loop: &EventLoop<()>, resolution: (usize, usize)) -> Window {
+    let output_size = LogicalSize::new(resolution.0 as f64, resolution.1 as f64);
+    let window_size = LogicalSize::new((resolution.0 * WINDOW_SCALE) as f64, (resolution.1 * WINDOW_SCALE) as f64);
     WindowBuilder::new()
         .with_min_inner_size(output_size)
         .with_inner_size(window_size)
@@ -104,36 +632,73 @@ fn get_window(event_loop: &EventLoop<()>) -> Window {
         .unwrap()
 }
 
-fn get_pixels(window: &Window) -> Pixels {
+/// Shrinks the window to the largest whole multiple of `resolution` which fits in it, so every pixel of the display
+/// is drawn the same size, without a border around the screen.
+fn fit_window(window: &Window, resolution: (usize, usize)) {
+    let size = window.inner_size();
+    let (width, height) = (resolution.0 as u32, resolution.1 as u32);
+    let scale = (size.width / width).min(size.height / height).max(1);
+    if (size.width, size.height) != (width * scale, height * scale) {
+        window.set_inner_size(PhysicalSize::new(width * scale, height * scale));
+    }
+}
+
+fn get_pixels(window: &Window, resolution: (usize, usize)) -> Pixels {
     let window_size = window.inner_size();
     let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, window);
-    Pixels::new(64 as u32, 32 as u32, surface_texture).unwrap()
-}
-
-fn get_keyboard_mappings() -> HashMap<VirtualKeyCode, u8> {
-    HashMap::from([
-        (VirtualKeyCode::X,     0x0),
-        (VirtualKeyCode::Key1,  0x1),
-        (VirtualKeyCode::Key2,  0x2),
-        (VirtualKeyCode::Key3,  0x3),
-        (VirtualKeyCode::Q,     0x4),
-        (VirtualKeyCode::W,     0x5),
-        (VirtualKeyCode::E,     0x6),
-        (VirtualKeyCode::A,     0x7),
-        (VirtualKeyCode::S,     0x8),
-        (VirtualKeyCode::D,     0x9),
-        (VirtualKeyCode::Z,     0xA),
-        (VirtualKeyCode::C,     0xB),
-        (VirtualKeyCode::Key4,  0xC),
-        (VirtualKeyCode::R,     0xD),
-        (VirtualKeyCode::F,     0xE),
-        (VirtualKeyCode::V,     0xF)
-    ])
+    Pixels::new(resolution.0 as u32, resolution.1 as u32, surface_texture).unwrap()
+}
+
+fn get_keyboard_mappings(layout: Layout) -> HashMap<VirtualKeyCode, u8> {
+    match layout {
+        Layout::Player1 => HashMap::from([
+            (VirtualKeyCode::X,     0x0),
+            (VirtualKeyCode::Key1,  0x1),
+            (VirtualKeyCode::Key2,  0x2),
+            (VirtualKeyCode::Key3,  0x3),
+            (VirtualKeyCode::Q,     0x4),
+            (VirtualKeyCode::W,     0x5),
+            (VirtualKeyCode::E,     0x6),
+            (VirtualKeyCode::A,     0x7),
+            (VirtualKeyCode::S,     0x8),
+            (VirtualKeyCode::D,     0x9),
+            (VirtualKeyCode::Z,     0xA),
+            (VirtualKeyCode::C,     0xB),
+            (VirtualKeyCode::Key4,  0xC),
+            (VirtualKeyCode::R,     0xD),
+            (VirtualKeyCode::F,     0xE),
+            (VirtualKeyCode::V,     0xF)
+        ]),
+        Layout::Player2 => HashMap::from([
+            (VirtualKeyCode::Comma,     0x0),
+            (VirtualKeyCode::Key7,      0x1),
+            (VirtualKeyCode::Key8,      0x2),
+            (VirtualKeyCode::Key9,      0x3),
+            (VirtualKeyCode::U,         0x4),
+            (VirtualKeyCode::I,         0x5),
+            (VirtualKeyCode::O,         0x6),
+            (VirtualKeyCode::J,         0x7),
+            (VirtualKeyCode::K,         0x8),
+            (VirtualKeyCode::L,         0x9),
+            (VirtualKeyCode::M,         0xA),
+            (VirtualKeyCode::Period,    0xB),
+            (VirtualKeyCode::Key0,      0xC),
+            (VirtualKeyCode::P,         0xD),
+            (VirtualKeyCode::Semicolon, 0xE),
+            (VirtualKeyCode::Slash,     0xF)
+        ]),
+    }
+}
+
+/// Whether a control key was pressed. Keys which are part of the keypad only go to the program,
+/// so a layout covering a control (like I in the player 2 layout) disables it.
+fn control_pressed(input: &WinitInputHelper, mappings: &HashMap<VirtualKeyCode, u8>, key: VirtualKeyCode) -> bool {
+    input.key_pressed(key) && !mappings.contains_key(&key)
 }
 
 fn get_keys_pressed(input: &WinitInputHelper, mappings: &HashMap<VirtualKeyCode, u8>) -> Vec<u8> {
     mappings
-        .into_iter()
+        .iter()
         .filter(|m| input.key_held(*m.0))
         .map(|m| *m.1)
         .collect()
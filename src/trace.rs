@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use chip8_engine::{Instruction, Machine};
+use log::error;
+
+/// The log is flushed after this many lines, so a crash loses at most a fraction of a second of it.
+const FLUSH_INTERVAL: usize = 1000;
+
+/// The state of the machine right before an instruction ran. Its line is written once the next instruction
+/// comes along, which is when the registers it changed are known.
+struct PendingLine {
+    pc: usize,
+    opcode: u16,
+    instruction: Instruction,
+    registers: [u8; 16],
+    index: usize,
+}
+
+/// Writes a line to a file for every executed instruction, with the program counter, opcode,
+/// and the registers the instruction changed:
+///
+/// ```text
+/// 0x200: 0x6005  LD V0, 0x05  V0=0x05
+/// 0x202: 0xA2EA  LD I, 0x2EA  I=0x2EA
+/// ```
+pub struct Tracer {
+    /// `None` once writing has failed, after which tracing stops
+    writer: Option<BufWriter<File>>,
+    pending: Option<PendingLine>,
+    lines_since_flush: usize,
+}
+
+impl Tracer {
+    pub fn new(path: &str) -> io::Result<Self> {
+        Ok(Tracer { writer: Some(BufWriter::new(File::create(path)?)), pending: None, lines_since_flush: 0 })
+    }
+
+    /// Records an instruction which is about to run on `machine`, to be given to the machine's trace callback.
+    pub fn trace(&mut self, machine: &Machine, pc: usize, instruction: Instruction) {
+        self.write_pending(machine);
+        let opcode = machine.read_memory(pc, 2).map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+        self.pending = Some(PendingLine { pc, opcode, instruction, registers: machine.registers(), index: machine.index() });
+    }
+
+    /// Writes out the last instruction and flushes the log, for when the emulator exits.
+    pub fn finish(&mut self, machine: &Machine) {
+        self.write_pending(machine);
+        if let Some(writer) = &mut self.writer {
+            let result = writer.flush();
+            self.check(result);
+        }
+    }
+
+    /// Writes the line for the pending instruction, comparing the state it started from with `machine`.
+    fn write_pending(&mut self, machine: &Machine) {
+        let (pending, writer) = match (self.pending.take(), &mut self.writer) {
+            (Some(pending), Some(writer)) => (pending, writer),
+            _ => return,
+        };
+        let mut line = format!("{:#05x}: {:#06X}  {}", pending.pc, pending.opcode, pending.instruction);
+        for (register, (&before, &after)) in pending.registers.iter().zip(machine.registers().iter()).enumerate() {
+            if before != after {
+                line.push_str(&format!("  V{:X}={:#04X}", register, after));
+            }
+        }
+        if pending.index != machine.index() {
+            line.push_str(&format!("  I={:#05X}", machine.index()));
+        }
+
+        let mut result = writeln!(writer, "{}", line);
+        self.lines_since_flush += 1;
+        if result.is_ok() && self.lines_since_flush == FLUSH_INTERVAL {
+            self.lines_since_flush = 0;
+            result = writer.flush();
+        }
+        self.check(result);
+    }
+
+    /// Stops tracing if writing to the log failed.
+    fn check(&mut self, result: io::Result<()>) {
+        if let Err(e) = result {
+            error!("Unable to write trace, tracing has stopped: {}", e);
+            self.writer = None;
+        }
+    }
+}